@@ -11,17 +11,26 @@ pub trait GridNode: PartialEq + Eq + Clone + Hash + PartialOrd + Ord {
 /// A pathfinding map structure.
 ///
 /// A Dijkstra map lets you run pathfinding from any graph node it covers
-/// towards or away from the target nodes of the map. Currently the structure
-/// only supports underlying graphs with a fixed grid graph where the
-/// neighbors of each node must be the adjacent grid cells of that node.
+/// towards or away from the target nodes of the map. Downhill and uphill
+/// queries assume a fixed grid graph where the neighbors of each node are the
+/// adjacent grid cells of that node.
 pub struct Dijkstra<N> {
     pub weights: HashMap<N, u32>,
 }
 
-impl<N: GridNode> Dijkstra<N> {
-    /// Create a new Dijkstra map up to limit distance from goals, omitting
-    /// nodes for which the is_valid predicate returns false.
-    pub fn new<F: Fn(&N) -> bool>(goals: Vec<N>, is_valid: F, limit: u32) -> Dijkstra<N> {
+impl<N: Eq + Clone + Hash> Dijkstra<N> {
+    /// Create a new Dijkstra map with custom connectivity between nodes.
+    ///
+    /// Use this when the graph has connections that don't follow from `GridNode::neighbors`, such
+    /// as portals. The neighbors function must only return valid nodes. Distances are measured
+    /// from the goals along the connections the function returns, so if some connections only go
+    /// one way and the map is used to move towards the goals, the function must return the nodes
+    /// that connect to the given node instead of the ones it connects to.
+    pub fn new_with_neighbors<F: Fn(&N) -> Vec<N>>(
+        goals: Vec<N>,
+        neighbors: F,
+        limit: u32,
+    ) -> Dijkstra<N> {
         assert!(!goals.is_empty());
 
         let mut weights = HashMap::new();
@@ -38,8 +47,8 @@ impl<N: GridNode> Dijkstra<N> {
 
             let mut new_edge = HashSet::new();
             for n in &edge {
-                for m in n.neighbors() {
-                    if is_valid(&m) && !weights.contains_key(&m) {
+                for m in neighbors(n) {
+                    if !weights.contains_key(&m) {
                         new_edge.insert(m);
                     }
                 }
@@ -55,6 +64,59 @@ impl<N: GridNode> Dijkstra<N> {
         Dijkstra { weights }
     }
 
+    /// Create an inverted map for fleeing from the goals of this map.
+    ///
+    /// Moving downhill on the inverted map leads away from the goals. Unlike just moving uphill
+    /// on the original map, the inverted map will lead past the goals towards open space instead
+    /// of into a dead end when that's the better escape route. The neighbors function is the same
+    /// one used to build the original map.
+    pub fn inverted<F: Fn(&N) -> Vec<N>>(&self, neighbors: F) -> Dijkstra<N> {
+        // Scale distances by 6/5 so that going a bit towards the goals is worth it if it leads
+        // further away in the end.
+        let max = self.weights.values().cloned().max().unwrap_or(0);
+        let mut weights: HashMap<N, u32> = self
+            .weights
+            .iter()
+            .map(|(n, &w)| (n.clone(), (max - w) * 6 / 5))
+            .collect();
+
+        // Relax until every node is at most one step above its lowest neighbor.
+        let nodes: Vec<N> = weights.keys().cloned().collect();
+        loop {
+            let mut changed = false;
+            for n in &nodes {
+                let lowest = neighbors(n)
+                    .into_iter()
+                    .filter_map(|m| weights.get(&m).cloned())
+                    .min();
+                if let Some(lowest) = lowest {
+                    if lowest + 1 < weights[n] {
+                        weights.insert(n.clone(), lowest + 1);
+                        changed = true;
+                    }
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        Dijkstra { weights }
+    }
+}
+
+impl<N: GridNode> Dijkstra<N> {
+    /// Create a new Dijkstra map up to limit distance from goals, omitting
+    /// nodes for which the is_valid predicate returns false.
+    pub fn new<F: Fn(&N) -> bool>(goals: Vec<N>, is_valid: F, limit: u32) -> Dijkstra<N> {
+        Dijkstra::new_with_neighbors(
+            goals,
+            |n| n.neighbors().into_iter().filter(|m| is_valid(m)).collect(),
+            limit,
+        )
+    }
+
     /// Return the neighbors of a cell (if any), sorted from downhill to
     /// uphill.
     pub fn sorted_neighbors(&self, node: &N) -> Vec<N> {
@@ -72,8 +134,8 @@ impl<N: GridNode> Dijkstra<N> {
 /// Find A* path in freeform graph.
 ///
 /// The `neighbors` function returns neighboring nodes and their estimated distance from the goal.
-/// The search will treat any node whose distance is zero as a goal and return a path leading to
-/// it.
+/// Every step between neighboring nodes costs one unit of distance, so the estimate must not be
+/// larger than the actual number of steps to the goal for the search to find the shortest path.
 pub fn astar_path<N, F>(start: N, end: &N, neighbors: F) -> Option<Vec<N>>
 where
     N: Eq + Hash + Clone,
@@ -82,6 +144,7 @@ where
     #[derive(Eq, PartialEq)]
     struct MetricNode<N> {
         value: u32,
+        cost: u32,
         item: N,
         come_from: Option<N>,
     }
@@ -92,8 +155,10 @@ where
         fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
     }
 
-    fn node<N: Eq>(item: N, dist: f32, come_from: Option<N>) -> MetricNode<N> {
+    fn node<N: Eq>(item: N, cost: u32, dist: f32, come_from: Option<N>) -> MetricNode<N> {
         debug_assert!(dist >= 0.0);
+        // Total estimated length of the path going through this node.
+        let dist = cost as f32 + dist;
         // Convert dist to integers so we can push MetricNodes into BinaryHeap that expects Ord.
         // The trick here is that non-negative IEEE 754 floats have the same ordering as their
         // binary representations interpreted as integers.
@@ -104,6 +169,7 @@ where
         MetricNode {
             item,
             value,
+            cost,
             come_from,
         }
     }
@@ -111,7 +177,7 @@ where
     let mut come_from = HashMap::new();
 
     let mut open = BinaryHeap::new();
-    open.push(node(start.clone(), ::std::f32::MAX, None));
+    open.push(node(start.clone(), 0, 0.0, None));
 
     // Find shortest path.
     let mut goal = loop {
@@ -134,7 +200,12 @@ where
                 if already_seen {
                     continue;
                 }
-                open.push(node(item, dist, Some(closest.item.clone())));
+                open.push(node(
+                    item,
+                    closest.cost + 1,
+                    dist,
+                    Some(closest.item.clone()),
+                ));
             }
         } else {
             break None;
@@ -179,5 +250,78 @@ mod test {
             Some(vec![8, 9, 10, 11, 12]),
             astar_path(8, &12, |x| neighbors(8, x))
         );
+        // Shortcut connection between 0 and 15, the search must find it without a heuristic.
+        let shortcut = |&x: &i32| -> Vec<(i32, f32)> {
+            let mut ret = vec![(x - 1, 0.0), (x + 1, 0.0)];
+            match x {
+                0 => ret.push((15, 0.0)),
+                15 => ret.push((0, 0.0)),
+                _ => {}
+            }
+            ret
+        };
+        assert_eq!(Some(vec![0, 15, 14, 13]), astar_path(0, &13, shortcut));
+    }
+
+    #[test]
+    fn test_dijkstra_with_neighbors() {
+        // A line with a wraparound "portal" between the two ends.
+        let neighbors = |&x: &i32| -> Vec<i32> {
+            match x {
+                0 => vec![1, 9],
+                9 => vec![8, 0],
+                x => vec![x - 1, x + 1],
+            }
+        };
+
+        let map = Dijkstra::new_with_neighbors(vec![0], neighbors, 100);
+        assert_eq!(Some(&0), map.weights.get(&0));
+        assert_eq!(Some(&1), map.weights.get(&9));
+        assert_eq!(Some(&4), map.weights.get(&6));
+        assert_eq!(Some(&5), map.weights.get(&5));
+        assert_eq!(None, map.weights.get(&10));
+    }
+
+    #[test]
+    fn test_dijkstra_one_way() {
+        // A line from 0 to 9 with a one-way "portal" from 9 to 0.
+        let steps_into = |&x: &i32| -> Vec<i32> {
+            let mut ret: Vec<i32> =
+                [x - 1, x + 1].iter().cloned().filter(|x| (0..=9).contains(x)).collect();
+            if x == 0 {
+                ret.push(9);
+            }
+            ret
+        };
+
+        // Moving towards 0, the portal end is a single step away...
+        let map = Dijkstra::new_with_neighbors(vec![0], steps_into, 100);
+        assert_eq!(Some(&1), map.weights.get(&9));
+        // ...but moving towards 9, the portal is no use.
+        let map = Dijkstra::new_with_neighbors(vec![9], steps_into, 100);
+        assert_eq!(Some(&9), map.weights.get(&0));
+    }
+
+    #[test]
+    fn test_dijkstra_inverted() {
+        // Fleeing from 3 on a line from 0 to 9 where 0 is a dead end.
+        let neighbors = |&x: &i32| -> Vec<i32> {
+            [x - 1, x + 1]
+                .iter()
+                .cloned()
+                .filter(|x| (0..=9).contains(x))
+                .collect()
+        };
+
+        let map = Dijkstra::new_with_neighbors(vec![3], neighbors, 100);
+        let inverted = map.inverted(neighbors);
+
+        // Far end of the line is the best place to be.
+        assert_eq!(Some(&0), inverted.weights.get(&9));
+        assert!(inverted.weights[&5] < inverted.weights[&4]);
+        // Right next to the threat, running into the dead end is better than going past it.
+        assert!(inverted.weights[&1] < inverted.weights[&2]);
+        // At the end of the dead end there's nowhere left to go.
+        assert!(inverted.weights[&0] < inverted.weights[&1]);
     }
 }
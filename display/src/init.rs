@@ -123,6 +123,7 @@ pub fn entity_brushes() -> VecMap<Arc<Brush>> {
     ret.insert(Bug as usize, Builder::new("assets/mobs.png").color(0xFF00FFFF).mob(6*32, 0).finish());
     ret.insert(Ooze as usize, Builder::new("assets/mobs.png").color(LIGHTSKYBLUE).mob(7*32, 0).finish());
     ret.insert(Efreet as usize, Builder::new("assets/mobs.png").color(ORANGE).mob(0, 1*32).finish());
+    ret.insert(Hireling as usize, Builder::new("assets/mobs.png").color(SANDYBROWN).mob(0, 0).finish());
 
     // The serpent has a special sprite structure where it's split to the head and mound parts,
     // and the mound part doesn't move during the idle animation.
@@ -132,12 +133,39 @@ pub fn entity_brushes() -> VecMap<Arc<Brush>> {
         .finish());
 
     ret.insert(Sword as usize, Builder::new("assets/props.png").color(LIGHTGRAY).tile(4*32, 1*32).finish());
+    ret.insert(Bow as usize, Builder::new("assets/props.png").color(BURLYWOOD).tile(32, 2*32).finish());
+    ret.insert(Crossbow as usize, Builder::new("assets/props.png").color(SIENNA).tile(4*32, 2*32).finish());
     ret.insert(Helmet as usize, Builder::new("assets/props.png").color(LIGHTGRAY).tile(5*32, 1*32).finish());
     ret.insert(Armor as usize, Builder::new("assets/props.png").color(LIGHTGRAY).tile(2*32, 2*32).finish());
 
     ret.insert(Scroll1 as usize, Builder::new("assets/props.png").color(LIGHTYELLOW).tile(7*32, 2*32).finish());
+    ret.insert(Scroll2 as usize, Builder::new("assets/props.png").color(LIGHTBLUE).tile(7*32, 2*32).finish());
+    ret.insert(Scroll3 as usize, Builder::new("assets/props.png").color(LIGHTGREEN).tile(7*32, 2*32).finish());
+    ret.insert(Scroll4 as usize, Builder::new("assets/props.png").color(PINK).tile(7*32, 2*32).finish());
+    ret.insert(Scroll5 as usize, Builder::new("assets/props.png").color(WHEAT).tile(7*32, 2*32).finish());
     ret.insert(Wand1 as usize, Builder::new("assets/props.png").color(RED).tile(7*32, 1*32).finish());
     ret.insert(Wand2 as usize, Builder::new("assets/props.png").color(CYAN).tile(7*32, 1*32).finish());
+    ret.insert(Wand3 as usize, Builder::new("assets/props.png").color(LIMEGREEN).tile(7*32, 1*32).finish());
+    ret.insert(Wand4 as usize, Builder::new("assets/props.png").color(GOLD).tile(7*32, 1*32).finish());
+    ret.insert(Wand5 as usize, Builder::new("assets/props.png").color(VIOLET).tile(7*32, 1*32).finish());
+    ret.insert(Wand6 as usize, Builder::new("assets/props.png").color(SILVER).tile(7*32, 1*32).finish());
+    ret.insert(Potion1 as usize, Builder::new("assets/props.png").color(CRIMSON).tile(6*32, 1*32).finish());
+    ret.insert(Potion2 as usize, Builder::new("assets/props.png").color(ROYALBLUE).tile(6*32, 1*32).finish());
+    ret.insert(Potion3 as usize, Builder::new("assets/props.png").color(SPRINGGREEN).tile(6*32, 1*32).finish());
+    ret.insert(Potion4 as usize, Builder::new("assets/props.png").color(ORCHID).tile(6*32, 1*32).finish());
+    ret.insert(Potion5 as usize, Builder::new("assets/props.png").color(KHAKI).tile(6*32, 1*32).finish());
+    ret.insert(Ring as usize, Builder::new("assets/props.png").color(GOLD).tile(3*32, 1*32).finish());
+    ret.insert(Amulet as usize, Builder::new("assets/props.png").color(AQUAMARINE).tile(2*32, 1*32).finish());
+    ret.insert(Lantern as usize, Builder::new("assets/props.png").color(ORANGE).tile(5*32, 2*32).finish());
+    ret.insert(Bag as usize, Builder::new("assets/props.png").color(TAN).tile(6*32, 2*32).finish());
+    ret.insert(Box as usize, Builder::new("assets/props.png").color(PERU).tile(5*32, 0).finish());
+    ret.insert(Chest as usize, Builder::new("assets/props.png").color(SADDLEBROWN).tile(6*32, 0).finish());
+    ret.insert(Ration as usize, Builder::new("assets/props.png").color(BURLYWOOD).tile(3*32, 0).finish());
+    ret.insert(Apple as usize, Builder::new("assets/props.png").color(RED).tile(4*32, 0).finish());
+    ret.insert(Spellbook as usize, Builder::new("assets/props.png").color(MEDIUMPURPLE).tile(32, 32).finish());
+    ret.insert(Orb as usize, Builder::new("assets/props.png").color(LIGHTPINK).tile(3*32, 2*32).finish());
+    ret.insert(Gold as usize, Builder::new("assets/props.png").color(GOLD).tile(2*32, 0).finish());
+    ret.insert(Corpse as usize, Builder::new("assets/props.png").color(DARKRED).tile(0, 2*32).finish());
     ret
 }
 
@@ -179,7 +179,7 @@ impl WorldView {
             // FIXME: This should not use live entity data for the remembered objects, since it
             // will then show the object moving around without the player observing it.
             for &i in &items {
-                if let Some(desc) = world.ecs().desc.get(i) {
+                if let Some(icon) = world.entity_icon(i) {
                     let color = if in_map_memory {
                         Coloring::MapMemory
                     } else {
@@ -189,8 +189,7 @@ impl WorldView {
                         }
                     };
                     entity_sprite_buffer.push(
-                        Sprite::new(Layer::Object, screen_pos, cache::entity(desc.icon))
-                            .color(color),
+                        Sprite::new(Layer::Object, screen_pos, cache::entity(icon)).color(color),
                     );
                 }
             }
@@ -198,6 +197,14 @@ impl WorldView {
             // Draw mobs in directly seen cells
             if !in_map_memory {
                 for &i in &mobs {
+                    let hidden = match world.player() {
+                        Some(player) => i != player && world.is_hidden_from(player, i),
+                        None => world.is_invisible(i),
+                    };
+                    if hidden {
+                        continue;
+                    }
+
                     let mut mob_pos = screen_pos;
 
                     // Is the mob doing a tweening move, interpolate it's position between old and
@@ -1,26 +1,50 @@
 use calx::{color, Dir6, IncrementalState, Rgba};
+use calx_ecs::Entity;
 use display;
-use euclid::{Point2D, Rect};
+use euclid::{vec2, Point2D, Rect};
 use std::io::prelude::*;
 use vitral::{self, Align, Canvas, InputEvent, Keycode, RectUtil, Scene, SceneSwitch};
-use world::{ActionOutcome, Command, Event, ItemType, Location, Mutate, Query, Slot, World};
+use world::{
+    ActionError, Command, Event, Hunger, ItemType, Location, Mutate, Query, Slot, Terrain,
+    TerrainQuery, World,
+};
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 enum InventoryMode {
     Drop,
     Equip,
     Use,
+    /// Pick a container to look inside.
+    Open,
+    /// Pick an item to put in the container in the slot.
+    PutIn(Slot),
 }
 
+/// Browse the contents of the container in the slot.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct ContainerMode(Slot);
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 enum AimMode {
     Zap(Slot),
+    Cast(Slot),
+    Shoot,
     // Maybe add intrinsic abilities not tied to a specific entity later
 }
 
+/// Multi-turn player activities that keep issuing commands until done or interrupted.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Autopilot {
+    /// Walk into the unexplored parts of the map.
+    Explore { take_items: bool },
+    /// Walk to a remembered location.
+    Travel(Location),
+}
+
 pub(crate) struct GameRuntime {
     world: IncrementalState<World>,
     command: Option<Command>,
+    autopilot: Option<Autopilot>,
 }
 
 impl GameRuntime {
@@ -28,6 +52,7 @@ impl GameRuntime {
         GameRuntime {
             world: IncrementalState::new(seed),
             command: None,
+            autopilot: None,
         }
     }
 }
@@ -35,6 +60,10 @@ impl GameRuntime {
 pub struct GameLoop {
     pub console: display::Console,
     camera_loc: Location,
+    /// Number of world events already shown.
+    ///
+    /// The world keeps events from the waiting turns until the player's next command.
+    events_seen: usize,
 }
 
 enum Side {
@@ -49,6 +78,14 @@ impl AimMode {
                 ctx.command = Some(Command::Zap(*slot, dir));
                 Some(SceneSwitch::Pop)
             }
+            AimMode::Cast(slot) => {
+                ctx.command = Some(Command::CastAt(*slot, dir));
+                Some(SceneSwitch::Pop)
+            }
+            AimMode::Shoot => {
+                ctx.command = Some(Command::Shoot(dir));
+                Some(SceneSwitch::Pop)
+            }
         }
     }
 }
@@ -85,6 +122,69 @@ impl Scene<GameRuntime> for AimMode {
     fn draw_previous(&self) -> bool { true }
 }
 
+impl InventoryMode {
+    /// Return the command this mode issues for an inventory slot.
+    ///
+    /// Aimed commands get a placeholder direction, the actual direction is picked in aim mode.
+    /// Modes that open another menu instead of acting return `None`.
+    fn command(self, world: &World, slot: Slot) -> Option<Command> {
+        let cmd = match self {
+            InventoryMode::Drop => {
+                // Drop the whole stack.
+                let count = world
+                    .player()
+                    .and_then(|p| world.entity_equipped(p, slot))
+                    .map_or(1, |item| world.quantity(item));
+                Command::Drop(slot, count)
+            }
+            InventoryMode::Equip => Command::Equip(slot),
+            InventoryMode::Use => {
+                let item_type = world
+                    .player()
+                    .and_then(|p| world.entity_equipped(p, slot))
+                    .and_then(|item| world.item_type(item));
+                if let Some(ItemType::TargetedUsable(_)) = item_type {
+                    Command::Zap(slot, Dir6::North)
+                } else {
+                    Command::UseItem(slot)
+                }
+            }
+            InventoryMode::Open => return None,
+            InventoryMode::PutIn(container) => Command::PutIn(slot, container),
+        };
+        Some(cmd)
+    }
+
+    /// Return whether selecting the slot would do anything.
+    fn is_enabled(self, world: &World, slot: Slot) -> bool {
+        match self.command(world, slot) {
+            Some(cmd) => world.check_command(&cmd).is_ok(),
+            None => world
+                .player()
+                .and_then(|p| world.entity_equipped(p, slot))
+                .map_or(false, |item| world.is_container(item)),
+        }
+    }
+}
+
+/// Describe an inventory item along with curses and unmet equip requirements.
+fn item_label(world: &World, player: Entity, item: Entity) -> String {
+    let mut ret = world.noun(item).counted_name();
+    if world.is_cursed(item) {
+        ret.push_str(" (cursed)");
+    }
+    if world.equip_type(item).is_some() {
+        match world.check_requirements(player, item) {
+            Err(ActionError::TooWeak) => {
+                ret.push_str(&format!(" (needs power {})", world.requirements(item).min_power))
+            }
+            Err(ActionError::NoHands) => ret.push_str(" (needs hands)"),
+            _ => {}
+        }
+    }
+    ret
+}
+
 impl Scene<GameRuntime> for InventoryMode {
     fn render(
         &mut self,
@@ -103,6 +203,7 @@ impl Scene<GameRuntime> for InventoryMode {
         let mut slot_name_pos = Point2D::new(20, 0);
         let mut item_name_pos = Point2D::new(80, 0);
         let text_color = [1.0, 1.0, 1.0, 1.0];
+        let disabled_color = [0.5, 0.5, 0.5, 1.0];
 
         for slot in SLOT_DATA.iter() {
             // TODO: Bounding box for these is a button...
@@ -121,16 +222,23 @@ impl Scene<GameRuntime> for InventoryMode {
                 slot.name,
             );
             let item_name = if let Some(item) = ctx.world.entity_equipped(player, slot.slot) {
-                ctx.world.entity_name(item)
+                item_label(&ctx.world, player, item)
             } else {
                 "".to_string()
             };
 
+            // Grey out items the command can't be used on.
+            let item_color = if self.is_enabled(&ctx.world, slot.slot) {
+                text_color
+            } else {
+                disabled_color
+            };
+
             item_name_pos = canvas.draw_text(
                 &*display::font(),
                 item_name_pos,
                 Align::Left,
-                text_color,
+                item_color,
                 &item_name,
             );
         }
@@ -153,29 +261,24 @@ impl Scene<GameRuntime> for InventoryMode {
 
             for slot in SLOT_DATA.iter() {
                 if scancode == slot.code {
-                    match self {
-                        InventoryMode::Drop => ctx.command = Some(Command::Drop(slot.slot)),
-                        InventoryMode::Equip => ctx.command = Some(Command::Equip(slot.slot)),
-                        InventoryMode::Use => {
-                            // Need to see what happens when you use it.
-                            let player = ctx.world.player()?;
-                            let item = ctx.world.entity_equipped(player, slot.slot)?;
-
-                            match ctx.world.item_type(item) {
-                                Some(ItemType::UntargetedUsable(_)) => {
-                                    // No further input needed, just fire off the command
-                                    ctx.command = Some(Command::UseItem(slot.slot));
-                                }
-                                Some(ItemType::TargetedUsable(_)) => {
-                                    // Items needs aiming, switch to aim mode.
-                                    return Some(SceneSwitch::Replace(Box::new(AimMode::Zap(
-                                        slot.slot,
-                                    ))));
-                                }
-                                _ => {}
-                            }
+                    let cmd = match self.command(&ctx.world, slot.slot) {
+                        Some(cmd) => cmd,
+                        None if self.is_enabled(&ctx.world, slot.slot) => {
+                            let mode = ContainerMode(slot.slot);
+                            return Some(SceneSwitch::Replace(Box::new(mode)));
+                        }
+                        None => return Some(SceneSwitch::Pop),
+                    };
+                    if let Command::Zap(slot, _) = cmd {
+                        if ctx.world.check_command(&cmd).is_ok() {
+                            // Items needs aiming, switch to aim mode.
+                            return Some(SceneSwitch::Replace(Box::new(AimMode::Zap(slot))));
                         }
                     }
+
+                    // Invalid commands get their failure reason reported when the game loop
+                    // tries to run them.
+                    ctx.command = Some(cmd);
                     return Some(SceneSwitch::Pop);
                 }
             }
@@ -193,22 +296,119 @@ impl Scene<GameRuntime> for InventoryMode {
     fn draw_previous(&self) -> bool { true }
 }
 
+impl ContainerMode {
+    /// Menu keys for the container contents, one per letter.
+    fn keys() -> impl Iterator<Item = &'static SlotData> {
+        SLOT_DATA.iter().filter(|slot| slot.key.is_ascii_lowercase())
+    }
+
+    fn container(self, world: &World) -> Option<Entity> {
+        world.entity_equipped(world.player()?, self.0)
+    }
+}
+
+impl Scene<GameRuntime> for ContainerMode {
+    fn render(
+        &mut self,
+        ctx: &mut GameRuntime,
+        canvas: &mut Canvas,
+    ) -> Option<SceneSwitch<GameRuntime>> {
+        let (player, container) = match (ctx.world.player(), self.container(&ctx.world)) {
+            (Some(p), Some(c)) => (p, c),
+            _ => return Some(SceneSwitch::Pop),
+        };
+
+        let (_, bounds) = canvas.bounds().vertical_split(320);
+        canvas.fill_rect(&bounds, [0.0, 0.0, 0.0, 0.99]);
+
+        let text_color = [1.0, 1.0, 1.0, 1.0];
+        let disabled_color = [0.5, 0.5, 0.5, 1.0];
+
+        let mut pos = canvas.draw_text(
+            &display::font(),
+            Point2D::new(0, 0),
+            Align::Left,
+            text_color,
+            &format!("{} (tab to put items in)", ctx.world.noun(container).counted_name()),
+        );
+
+        let contents = ctx.world.contents(container);
+        for (idx, (key, &item)) in ContainerMode::keys().zip(&contents).enumerate() {
+            let item_color = if ctx.world.check_command(&Command::TakeOut(self.0, idx)).is_ok() {
+                text_color
+            } else {
+                disabled_color
+            };
+
+            pos = canvas.draw_text(
+                &display::font(),
+                pos,
+                Align::Left,
+                item_color,
+                &format!("{}) {}", key.key, item_label(&ctx.world, player, item)),
+            );
+        }
+
+        None
+    }
+
+    fn input(
+        &mut self,
+        ctx: &mut GameRuntime,
+        event: InputEvent,
+    ) -> Option<SceneSwitch<GameRuntime>> {
+        if let InputEvent::KeyEvent {
+            is_down: true,
+            hardware_key: Some(scancode),
+            ..
+        } = event
+        {
+            use Keycode::*;
+
+            let container = self.container(&ctx.world)?;
+            let count = ctx.world.contents(container).len();
+            for (idx, key) in ContainerMode::keys().take(count).enumerate() {
+                if scancode == key.code {
+                    ctx.command = Some(Command::TakeOut(self.0, idx));
+                    return Some(SceneSwitch::Pop);
+                }
+            }
+
+            match scancode {
+                Tab => {
+                    let mode = InventoryMode::PutIn(self.0);
+                    return Some(SceneSwitch::Replace(Box::new(mode)));
+                }
+                Escape => {
+                    return Some(SceneSwitch::Pop);
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn draw_previous(&self) -> bool { true }
+}
+
 impl Scene<GameRuntime> for GameLoop {
     fn update(&mut self, ctx: &mut GameRuntime) -> Option<SceneSwitch<GameRuntime>> {
         if ctx.world.player_can_act() {
-            if let Some(cmd) = ctx.command {
-                ctx.world.update(cmd);
-                ctx.command = None;
-                for e in ctx.world.events() {
-                    match e {
-                        Event::Msg(text) => {
-                            let _ = writeln!(&mut self.console, "{}", text);
-                        }
-                        Event::Damage { entity, amount } => {
-                            let name = ctx.world.entity_name(*entity);
-                            // TODO: Use graphical effect
-                            let _ = writeln!(&mut self.console, "{} dmg {}", name, amount);
-                        }
+            if ctx.command.is_none() {
+                ctx.command = self.autopilot_command(ctx);
+            }
+
+            if let Some(cmd) = ctx.command.take() {
+                if let Err(err) = ctx.world.check_command(&cmd) {
+                    let _ = writeln!(&mut self.console, "{}", err);
+                    ctx.autopilot = None;
+                } else {
+                    // Player commands start a new batch of events.
+                    self.events_seen = 0;
+                    ctx.world.update(cmd);
+                    // Picking up items is part of exploring, don't stop for the pickup message.
+                    if self.process_events(ctx) && cmd != Command::Take {
+                        ctx.autopilot = None;
                     }
                 }
             } else {
@@ -216,15 +416,24 @@ impl Scene<GameRuntime> for GameLoop {
             }
         } else {
             // When playing turn-based and running the animations between player's inputs, speed
-            // things up so that the pace feels snappy.
-            const FAST_FORWARD: usize = 3;
+            // things up so that the pace feels snappy. Long stretches where the player can't act,
+            // like being asleep, get skipped in one go.
+            const FAST_FORWARD: u64 = 3;
+            const LONG_WAIT: u64 = 36;
+
+            let ticks = match ctx.world.ticks_until_player_acts() {
+                Some(ticks) if ticks > LONG_WAIT => ticks,
+                _ => FAST_FORWARD,
+            };
 
-            for _ in 0..FAST_FORWARD {
+            for _ in 0..ticks {
                 if ctx.world.player_can_act() {
                     break;
                 }
-                // TODO FIXME process events in return value.
                 ctx.world.update(Command::Wait);
+                if self.process_events(ctx) {
+                    ctx.autopilot = None;
+                }
             }
         }
 
@@ -252,8 +461,14 @@ impl Scene<GameRuntime> for GameLoop {
         view.draw(&*ctx.world, canvas);
         canvas.clear_clip();
 
+        if canvas.click_state(&view_area).left_clicked() {
+            if let Some(loc) = view.cursor_loc {
+                ctx.autopilot = Some(Autopilot::Travel(loc));
+            }
+        }
+
         canvas.set_clip(status_area);
-        self.status_draw(canvas, &status_area);
+        self.status_draw(&ctx.world, canvas, &status_area);
         canvas.clear_clip();
 
         let mut console_area = screen_area;
@@ -276,6 +491,9 @@ impl Scene<GameRuntime> for GameLoop {
         {
             use Keycode::*;
 
+            // Any key press interrupts autopilot.
+            ctx.autopilot = None;
+
             match scancode {
                 Q | Pad7 | Home => {
                     self.smart_step(ctx, Dir6::Northwest);
@@ -309,6 +527,30 @@ impl Scene<GameRuntime> for GameLoop {
                     ctx.command = Some(Command::Take);
                 }
 
+                O => {
+                    ctx.autopilot = Some(Autopilot::Explore { take_items: true });
+                }
+                X => {
+                    ctx.autopilot = Some(Autopilot::Explore { take_items: false });
+                }
+                Period => {
+                    let player = ctx.world.player()?;
+                    if let Some(loc) = ctx.world.nearest_remembered(player, Terrain::Exit) {
+                        ctx.autopilot = Some(Autopilot::Travel(loc));
+                    } else {
+                        let _ = writeln!(&mut self.console, "You don't know where the exit is.");
+                    }
+                }
+
+                F => {
+                    // Direction doesn't matter, just check if you can shoot at all.
+                    if let Err(err) = ctx.world.check_command(&Command::Shoot(Dir6::North)) {
+                        let _ = writeln!(&mut self.console, "{}", err);
+                    } else {
+                        return Some(SceneSwitch::Push(Box::new(AimMode::Shoot)));
+                    }
+                }
+
                 I => {
                     return Some(SceneSwitch::Push(Box::new(InventoryMode::Equip)));
                 }
@@ -318,6 +560,14 @@ impl Scene<GameRuntime> for GameLoop {
                 U => {
                     return Some(SceneSwitch::Push(Box::new(InventoryMode::Use)));
                 }
+                C => {
+                    return Some(SceneSwitch::Push(Box::new(InventoryMode::Open)));
+                }
+
+                Num1 | Num2 | Num3 | Num4 | Num5 | Num6 | Num7 | Num8 => {
+                    let slot = SLOT_DATA.iter().find(|s| s.code == scancode)?.slot;
+                    return self.cast(ctx, slot);
+                }
 
                 _ => {}
             }
@@ -331,11 +581,113 @@ impl GameLoop {
         GameLoop {
             console: display::Console::new(display::font()),
             camera_loc: Location::new(0, 0, 0),
+            events_seen: 0,
         }
     }
 
+    /// Cast the spell in a spell slot, switching to aim mode if it needs a direction.
+    fn cast(&mut self, ctx: &mut GameRuntime, slot: Slot) -> Option<SceneSwitch<GameRuntime>> {
+        let player = ctx.world.player()?;
+        let targeted = ctx
+            .world
+            .entity_equipped(player, slot)
+            .and_then(|spell| ctx.world.spell(spell))
+            .map_or(false, |spell| spell.targeted);
+
+        if targeted {
+            // Direction doesn't matter, just check if the spell can be cast at all.
+            if let Err(err) = ctx.world.check_command(&Command::CastAt(slot, Dir6::North)) {
+                let _ = writeln!(&mut self.console, "{}", err);
+                return None;
+            }
+            return Some(SceneSwitch::Push(Box::new(AimMode::Cast(slot))));
+        }
+
+        // Failures get reported when the game loop runs the command.
+        ctx.command = Some(Command::Cast(slot));
+        None
+    }
+
+    /// Show the events from the latest world update.
+    ///
+    /// Return whether there were any new messages.
+    fn process_events(&mut self, ctx: &mut GameRuntime) -> bool {
+        let player = ctx.world.player();
+        let events = ctx.world.events();
+        let mut saw_message = false;
+        for e in &events[self.events_seen.min(events.len())..] {
+            match e {
+                Event::Msg(text) => {
+                    let _ = writeln!(&mut self.console, "{}", text);
+                    saw_message = true;
+                }
+                Event::Damage { entity, amount } => {
+                    // TODO: Use graphical effect
+                    if Some(*entity) == player && *amount > 0 {
+                        ctx.autopilot = None;
+                    }
+                }
+                Event::LevelUp { entity, .. } => {
+                    // Let the player take a moment to notice.
+                    if Some(*entity) == player {
+                        ctx.autopilot = None;
+                    }
+                }
+            }
+        }
+        self.events_seen = events.len();
+        saw_message
+    }
+
+    /// Generate the next command from the active autopilot.
+    ///
+    /// Autopilot is switched off when it runs out of things to do or when the player sees an
+    /// enemy.
+    fn autopilot_command(&mut self, ctx: &mut GameRuntime) -> Option<Command> {
+        let autopilot = ctx.autopilot?;
+        let player = ctx.world.player()?;
+
+        if !ctx.world.visible_hostiles(player).is_empty() {
+            ctx.autopilot = None;
+            let _ = writeln!(&mut self.console, "You see an enemy.");
+            return None;
+        }
+
+        let cmd = match autopilot {
+            Autopilot::Explore { take_items } => {
+                let loc = ctx.world.location(player)?;
+                if take_items
+                    && ctx.world.item_at(loc).is_some()
+                    && ctx.world.free_bag_slot(player).is_some()
+                {
+                    Some(Command::Take)
+                } else {
+                    let dir = ctx.world.explore_dir(player, take_items);
+                    if dir.is_none() {
+                        let _ = writeln!(&mut self.console, "Nothing left to explore.");
+                    }
+                    dir.map(Command::Step)
+                }
+            }
+            Autopilot::Travel(destination) => {
+                // Traveling into a portal ends up on the other side.
+                let destination = ctx.world.portal(destination).unwrap_or(destination);
+                let dir = ctx.world.travel_dir(player, destination);
+                if dir.is_none() && ctx.world.location(player) != Some(destination) {
+                    let _ = writeln!(&mut self.console, "You don't know a way there.");
+                }
+                dir.map(Command::Step)
+            }
+        };
+
+        if cmd.is_none() {
+            ctx.autopilot = None;
+        }
+        cmd
+    }
+
     /// Step command that turns into melee attack if an enemy is in the way.
-    fn smart_step(&self, ctx: &mut GameRuntime, dir: Dir6) -> ActionOutcome {
+    fn smart_step(&self, ctx: &mut GameRuntime, dir: Dir6) -> Option<()> {
         let player = ctx.world.player()?;
         let loc = ctx.world.location(player)?;
         let destination = loc.jump(&*ctx.world, dir);
@@ -345,8 +697,7 @@ impl GameLoop {
                 // Fight on!
                 ctx.command = Some(Command::Melee(dir));
             } else {
-                // Do we want to do something smarter than walk into friendlies?
-                // The world might treat this as a displace action so keep it like this for now.
+                // Stepping into a friendly makes you swap places with it.
                 ctx.command = Some(Command::Step(dir));
             }
         } else {
@@ -355,7 +706,7 @@ impl GameLoop {
         Some(())
     }
 
-    fn side_step(&self, ctx: &mut GameRuntime, side: Side) -> ActionOutcome {
+    fn side_step(&self, ctx: &mut GameRuntime, side: Side) -> Option<()> {
         let player = ctx.world.player()?;
         let loc = ctx.world.location(player)?;
         let flip = (loc.x + loc.y) % 2 == 0;
@@ -380,14 +731,56 @@ impl GameLoop {
         self.smart_step(ctx, actual_dir)
     }
 
-    pub fn status_draw(&self, canvas: &mut Canvas, area: &Rect<i32>) {
+    pub fn status_draw(&self, world: &World, canvas: &mut Canvas, area: &Rect<i32>) {
         canvas.fill_rect(area, Rgba::from(0x33_11_11_ff).into());
+
+        let player = match world.player() {
+            Some(player) => player,
+            None => return,
+        };
+
+        if let Some(experience) = world.experience(player) {
+            canvas.draw_text(
+                &display::font(),
+                area.origin,
+                Align::Left,
+                color::WHITE.into(),
+                &format!(
+                    "Level {}  XP {}/{}",
+                    experience.level,
+                    experience.xp,
+                    experience.next_level_xp()
+                ),
+            );
+        }
+
+        let max_mana = world.max_mana(player);
+        if max_mana > 0 {
+            canvas.draw_text(
+                &display::font(),
+                area.origin + vec2(200, 0),
+                Align::Left,
+                color::LIGHTSKYBLUE.into(),
+                &format!("MP {}/{}", world.mana(player), max_mana),
+            );
+        }
+
+        let hunger = match world.hunger(player) {
+            Some(hunger) => hunger,
+            None => return,
+        };
+        let hunger_color = match hunger {
+            Hunger::Satiated => color::LIGHTGREEN,
+            Hunger::Hungry => color::YELLOW,
+            Hunger::Weak => color::ORANGE,
+            Hunger::Fainting => color::RED,
+        };
         canvas.draw_text(
             &*display::font(),
-            area.origin,
+            area.origin + vec2(300, 0),
             Align::Left,
-            color::RED.into(),
-            "Welcome to status bar",
+            hunger_color.into(),
+            &hunger.to_string(),
         );
     }
 }
@@ -1,24 +1,182 @@
+use crate::item::ItemType;
 use crate::location::{Location, Sector};
 use crate::map::Map;
-use crate::spec::{self, EntitySpawn};
+use crate::spec::{self, EntitySpawn, Spec};
 use crate::vaults;
 use crate::{Distribution, Rng};
 use calx::{self, die, RngExt, WeightedChoice};
 use log::debug;
 use rand::seq::SliceRandom;
 use rand::Rng as _Rng;
+use serde_derive::Deserialize;
 use std::error::Error;
 use std::str::FromStr;
 use std::sync::Arc;
 
+/// Weighted group of specs in a spawn table.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnGroup {
+    /// Relative probability of the group being picked from its table.
+    weight: f32,
+    /// Shallowest depth where the group spawns.
+    #[serde(default)]
+    min_depth: i32,
+    /// Deepest depth where the group spawns.
+    #[serde(default = "unlimited_depth")]
+    max_depth: i32,
+    /// Members of the group, picked by their spec rarity.
+    #[serde(deserialize_with = "spec::de_spawn_names")]
+    specs: Vec<EntitySpawn>,
+}
+
+fn unlimited_depth() -> i32 { i32::MAX }
+
+impl SpawnGroup {
+    /// Return the members of the group that can spawn at depth and pass the filter.
+    fn members<'a>(
+        &'a self,
+        depth: i32,
+        filter: &'a dyn Fn(&dyn Spec) -> bool,
+    ) -> impl Iterator<Item = &'a EntitySpawn> + 'a {
+        let in_range = (self.min_depth..=self.max_depth).contains(&depth);
+        self.specs.iter().filter(move |s| {
+            let spec = s.spec();
+            in_range && spec.rarity() != 0.0 && spec.min_depth() <= depth && filter(&**spec)
+        })
+    }
+
+    /// Pick a member of the group that can spawn at depth and passes the filter.
+    fn sample(
+        &self,
+        rng: &mut Rng,
+        depth: i32,
+        filter: &dyn Fn(&dyn Spec) -> bool,
+    ) -> Option<EntitySpawn> {
+        self.members(depth, filter)
+            .weighted_choice(rng, |s| 1.0 / s.spec().rarity())
+            .cloned()
+    }
+}
+
+/// Weighted table of spawn groups.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct SpawnTable(Vec<SpawnGroup>);
+
+impl SpawnTable {
+    /// Pick a spec from the table that can spawn at depth and passes the filter.
+    ///
+    /// Return `None` if nothing in the table fits.
+    pub fn sample(
+        &self,
+        rng: &mut Rng,
+        depth: i32,
+        filter: &dyn Fn(&dyn Spec) -> bool,
+    ) -> Option<EntitySpawn> {
+        self.0
+            .iter()
+            .weighted_choice(rng, |g| {
+                if g.members(depth, filter).next().is_some() {
+                    g.weight
+                } else {
+                    0.0
+                }
+            })
+            .and_then(|g| g.sample(rng, depth, filter))
+    }
+}
+
+/// Name of the biome for the regular dungeon levels.
+pub const DUNGEON_BIOME: &str = "dungeon";
+
+/// Spawn tables for a region of the game world, loaded from the spec data files.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BiomeSpec {
+    name: String,
+    monsters: SpawnTable,
+    items: SpawnTable,
+    /// Probability of a random spawn being a monster instead of an item.
+    monster_chance: f32,
+    /// Groups that spawn once on every level regardless of the rooms.
+    #[serde(default)]
+    guaranteed: Vec<SpawnGroup>,
+    /// Probability of a spawn being rolled as if it were further down.
+    #[serde(default)]
+    out_of_depth_chance: f32,
+    /// Maximum number of levels an out-of-depth spawn can come from below.
+    #[serde(default)]
+    out_of_depth_range: i32,
+}
+
+impl BiomeSpec {
+    pub fn name(&self) -> &str { &self.name }
+
+    /// Iterate the names of all the specs the biome can spawn.
+    pub(crate) fn spawn_names(&self) -> impl Iterator<Item = &EntitySpawn> {
+        self.monsters
+            .0
+            .iter()
+            .chain(&self.items.0)
+            .chain(&self.guaranteed)
+            .flat_map(|g| g.specs.iter())
+    }
+}
+
 /// Descriptor for different regions of the game world for spawn distributions.
 pub struct Biome {
+    spec: &'static BiomeSpec,
     depth: i32,
     // TODO: Branch specifications go here.
 }
 
 impl Biome {
-    pub fn new(depth: i32) -> Biome { Biome { depth } }
+    /// Make the regular dungeon biome for a depth.
+    pub fn new(depth: i32) -> Biome {
+        let spec = spec::iter_biomes()
+            .find(|b| b.name == DUNGEON_BIOME)
+            .expect("Dungeon biome not in spec database");
+        Biome { spec, depth }
+    }
+
+    /// Roll the depth a single spawn is picked for.
+    ///
+    /// Usually this is the biome depth, but sometimes things wander up from below.
+    fn spawn_depth(&self, rng: &mut Rng) -> i32 {
+        let spec = self.spec;
+        if spec.out_of_depth_range > 0 && rng.with_chance(spec.out_of_depth_chance) {
+            self.depth + rng.gen_range(1, spec.out_of_depth_range + 1)
+        } else {
+            self.depth
+        }
+    }
+
+    /// Roll the items found inside a container in the biome.
+    pub fn sample_loot(&self, rng: &mut Rng, capacity: u32) -> Vec<EntitySpawn> {
+        const MAX_LOOT: u32 = 4;
+
+        let count = rng.gen_range(1, capacity.min(MAX_LOOT) + 1);
+        (0..count)
+            .filter_map(|_| {
+                let depth = self.spawn_depth(rng);
+                self.spec.items.sample(rng, depth, &|spec| match spec.item_type() {
+                    // No nesting containers, and gold stays on the floor where it's picked up.
+                    None | Some(ItemType::Container(_)) | Some(ItemType::Gold) => false,
+                    Some(_) => true,
+                })
+            })
+            .collect()
+    }
+
+    /// Roll the spawns every level of the biome gets.
+    pub fn sample_guaranteed(&self, rng: &mut Rng) -> Vec<EntitySpawn> {
+        self.spec
+            .guaranteed
+            .iter()
+            .filter_map(|g| g.sample(rng, self.depth, &|_| true))
+            .collect()
+    }
 }
 
 struct Entrance(Arc<Map>);
@@ -88,11 +246,20 @@ impl Distribution<Dungeon> for Biome {
             let room = vaults::EXITS.choose(rng).unwrap();
             gen.place_room(rng, &*room)?;
 
-            if let Some(map) = gen.join_disjoint_regions(rng) {
-                Ok(map)
-            } else {
-                die!("Failed to join map");
+            let mut map = match gen.join_disjoint_regions(rng) {
+                Some(map) => map,
+                None => die!("Failed to join map"),
+            };
+
+            debug!("Placing guaranteed spawns");
+            let stairs = [map.entrances(), map.exits()].concat();
+            let floor: Vec<_> =
+                map.open_ground().into_iter().filter(|p| !stairs.contains(p)).collect();
+            for spawn in biome.sample_guaranteed(rng) {
+                map.push_spawn(*floor.choose(rng).unwrap(), spawn);
             }
+
+            Ok(map)
         }
 
         Dungeon(calx::retry_gen(16, rng, |rng| gen(rng, self)).expect("Couldn't generate map"))
@@ -101,16 +268,150 @@ impl Distribution<Dungeon> for Biome {
 
 impl Distribution<EntitySpawn> for Biome {
     fn sample(&self, rng: &mut Rng) -> EntitySpawn {
-        let item = spec::iter_specs()
-            .weighted_choice(rng, |item| {
-                if item.rarity() == 0.0 || item.min_depth() > self.depth {
-                    0.0
-                } else {
-                    1.0 / item.rarity()
-                }
-            })
-            .unwrap();
+        let table = if rng.with_chance(self.spec.monster_chance) {
+            &self.spec.monsters
+        } else {
+            &self.spec.items
+        };
+        let depth = self.spawn_depth(rng);
+        table
+            .sample(rng, depth, &|_| true)
+            .or_else(|| table.sample(rng, self.depth, &|_| true))
+            .unwrap_or_else(|| sample_any(rng, self.depth))
+    }
+}
+
+/// Pick any spec that can spawn at depth, weighted by rarity.
+///
+/// Used when the biome's spawn tables have nothing for the depth.
+fn sample_any(rng: &mut Rng, depth: i32) -> EntitySpawn {
+    let mut specs: Vec<_> = spec::iter_specs()
+        .filter(|s| s.rarity() != 0.0 && s.min_depth() <= depth)
+        .collect();
+    // Fixed order so the same seed gives the same spawn.
+    specs.sort_by(|a, b| a.name().cmp(b.name()));
+    let spec = specs
+        .iter()
+        .weighted_choice(rng, |s| 1.0 / s.rarity())
+        .expect("No specs can spawn at depth");
+    EntitySpawn::from_str(spec.name()).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Biome, BiomeSpec, SpawnTable};
+    use crate::item::ItemType;
+    use crate::spec::{self, EntitySpawn};
+    use crate::Distribution;
+    use calx::seeded_rng;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    const SEEDS: u32 = 2000;
+
+    fn spawn(name: &str) -> EntitySpawn { EntitySpawn::from_str(name).unwrap() }
+
+    #[test]
+    fn test_spawn_group_weights() {
+        let table: SpawnTable = ron::de::from_str(
+            "[
+                (weight: 3.0, specs: [\"dreg\"]),
+                (weight: 1.0, specs: [\"snake\"]),
+                (weight: 100.0, min_depth: 1, max_depth: 2, specs: [\"ogre\"]),
+            ]",
+        )
+        .unwrap();
+
+        let mut counts: HashMap<EntitySpawn, u32> = HashMap::new();
+        for seed in 0..SEEDS {
+            let mut rng = seeded_rng(&seed);
+            let s = table.sample(&mut rng, 5, &|_| true).unwrap();
+            *counts.entry(s).or_insert(0) += 1;
+        }
+
+        // Groups past their max depth never show up.
+        assert_eq!(counts.get(&spawn("ogre")), None);
+        let dregs = counts[&spawn("dreg")] as f32 / SEEDS as f32;
+        assert!((dregs - 0.75).abs() < 0.05, "dreg frequency {}", dregs);
 
-        EntitySpawn::from_str(item.name()).unwrap()
+        // Nothing fits, nothing spawns.
+        let mut rng = seeded_rng(&0);
+        assert_eq!(table.sample(&mut rng, 1, &|s| s.name() == "bug"), None);
+    }
+
+    #[test]
+    fn test_biome_spawns() {
+        let biome = Biome::new(1);
+
+        let mut out_of_depth = 0;
+        let mut monsters = 0;
+        for seed in 0..SEEDS {
+            let mut rng = seeded_rng(&seed);
+            let s: EntitySpawn = biome.sample(&mut rng);
+            let spec = s.spec();
+            assert!(spec.min_depth() <= 1 + biome.spec.out_of_depth_range, "{} too deep", s);
+            if spec.min_depth() > 1 {
+                out_of_depth += 1;
+            }
+            if spec.item_type().is_none() {
+                monsters += 1;
+            }
+        }
+
+        let monsters = monsters as f32 / SEEDS as f32;
+        let expected = biome.spec.monster_chance;
+        assert!((monsters - expected).abs() < 0.05, "monster frequency {}", monsters);
+        // Only the out-of-depth rolls can bring up deeper spawns.
+        let out_of_depth = out_of_depth as f32 / SEEDS as f32;
+        assert!(out_of_depth > 0.0 && out_of_depth <= biome.spec.out_of_depth_chance + 0.02);
+    }
+
+    #[test]
+    fn test_empty_tables() {
+        // Depths the tables don't cover get spawns from the whole spec database.
+        let spec: BiomeSpec =
+            ron::de::from_str("(name: \"void\", monsters: [], items: [], monster_chance: 0.5)")
+                .unwrap();
+        let biome = Biome {
+            spec: Box::leak(Box::new(spec)),
+            depth: 1,
+        };
+        for seed in 0..SEEDS / 10 {
+            let mut rng = seeded_rng(&seed);
+            let s: EntitySpawn = biome.sample(&mut rng);
+            assert!(s.spec().rarity() != 0.0 && s.spec().min_depth() <= 1, "{} can't spawn", s);
+        }
+    }
+
+    #[test]
+    fn test_guaranteed_spawns() {
+        for depth in 1..=10 {
+            let biome = Biome::new(depth);
+            for seed in 0..SEEDS / 10 {
+                let mut rng = seeded_rng(&(seed, depth));
+                let spawns = biome.sample_guaranteed(&mut rng);
+                assert_eq!(spawns.len(), biome.spec.guaranteed.len());
+                assert!(spawns.iter().any(|s| matches!(
+                    s.spec().item_type(),
+                    Some(ItemType::MeleeWeapon) | Some(ItemType::RangedWeapon)
+                )));
+            }
+        }
+    }
+
+    #[test]
+    fn test_tables_cover_specs() {
+        // Every spec that's supposed to spawn randomly should be in a spawn table.
+        let biome = Biome::new(1);
+        for s in spec::iter_specs().filter(|s| s.rarity() != 0.0) {
+            let s = spawn(s.name());
+            assert!(
+                [&biome.spec.monsters, &biome.spec.items]
+                    .iter()
+                    .any(|t| t.0.iter().any(|g| g.specs.contains(&s))),
+                "{} not in any spawn table",
+                s
+            );
+        }
     }
 }
@@ -1,15 +1,97 @@
-use crate::item::Slot;
+use crate::components::Status;
+use crate::item::{ItemType, Slot};
 use crate::mutate::Mutate;
 use crate::query::Query;
 use crate::world::World;
 use calx::Dir6;
 use calx::Incremental;
 use serde_derive::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
 
 /// Return type for actions that might fail.
 ///
 /// Used for early exit with ?-operator in the action functions.
-pub type ActionOutcome = Option<()>;
+pub type ActionOutcome = Result<(), ActionError>;
+
+/// Reasons for an action to fail.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ActionError {
+    /// The acting entity isn't placed in the world.
+    NotInWorld,
+    /// Terrain or another creature is in the way.
+    Blocked,
+    /// There is nothing to attack in the direction.
+    NoTarget,
+    /// Trying to shoot without a ranged weapon.
+    NoRangedWeapon,
+    /// There are no items at the location.
+    NothingToTake,
+    /// The entity can't pick up the item.
+    CannotTake,
+    /// There's no free inventory slot.
+    BagFull,
+    /// There's no item in the given slot.
+    EmptySlot,
+    /// The item isn't equipment.
+    CannotEquip,
+    /// All equipment slots for the item are already taken.
+    NoFreeSlot,
+    /// The item can't be used this way.
+    NotUsable,
+    /// The item has no charges left.
+    NoCharges,
+    /// Trying to drop zero items.
+    NothingToDrop,
+    /// The equipped item is cursed and won't come off.
+    Cursed,
+    /// The entity is too weak to equip the item.
+    TooWeak,
+    /// The item needs hands to equip.
+    NoHands,
+    /// Trying to put things in something that isn't a container.
+    NotAContainer,
+    /// The item can't go inside a container.
+    CannotContain,
+    /// The container has no room for the item.
+    ContainerFull,
+    /// Not enough mana to cast the spell.
+    NoMana,
+    /// The spell was cast too recently.
+    Cooldown,
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ActionError::*;
+        let msg = match self {
+            NotInWorld => "You are not in the world.",
+            Blocked => "Something is in the way.",
+            NoTarget => "There's nothing there to attack.",
+            NoRangedWeapon => "You have no ranged weapon.",
+            NothingToTake => "There's nothing here to pick up.",
+            CannotTake => "You can't pick that up.",
+            BagFull => "You have no room in your bag.",
+            EmptySlot => "You have nothing in that slot.",
+            CannotEquip => "You can't equip that.",
+            NoFreeSlot => "You have no free slot to equip that in.",
+            NotUsable => "You can't use that.",
+            NoCharges => "Nothing happens.",
+            NothingToDrop => "You drop nothing at all.",
+            Cursed => "You can't take that off, it's cursed.",
+            TooWeak => "You aren't strong enough to use that.",
+            NoHands => "You need hands to use that.",
+            NotAContainer => "That isn't a container.",
+            CannotContain => "That won't fit in there.",
+            ContainerFull => "There's no more room in there.",
+            NoMana => "You don't have enough mana.",
+            Cooldown => "That spell isn't ready yet.",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+impl Error for ActionError {}
 
 /// Player command events that the world is updated with.
 #[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -22,12 +104,16 @@ pub enum Command {
     Step(Dir6),
     /// Melee attack in direction.
     Melee(Dir6),
+    /// Fire equipped ranged weapon in direction.
+    Shoot(Dir6),
     /// Pick up the topmost item from the floor where you're standing on.
     ///
     /// TODO: Item selection support.
     Take,
-    /// Drop an item from inventory slot.
-    Drop(Slot),
+    /// Drop a number of items from the stack in inventory slot.
+    ///
+    /// Counts larger than the stack drop the whole stack.
+    Drop(Slot, u32),
     /// Equip or unequip an item in slot.
     ///
     /// Items in equipment slots are unequipped to inventory. Items in inventory slots are equipped
@@ -37,6 +123,14 @@ pub enum Command {
     UseItem(Slot),
     /// Use a directionally targeted inventory item.
     Zap(Slot, Dir6),
+    /// Put the item in the first slot inside the container in the second slot.
+    PutIn(Slot, Slot),
+    /// Take an item out of the container in slot, indexed by its position in the contents.
+    TakeOut(Slot, usize),
+    /// Cast a nontargeted spell from a spell slot.
+    Cast(Slot),
+    /// Cast a directionally targeted spell from a spell slot.
+    CastAt(Slot, Dir6),
 }
 
 impl Incremental for World {
@@ -49,7 +143,7 @@ impl Incremental for World {
         if self.player_can_act() {
             debug_assert!(*e != Command::Wait, "Calling wait during player's turn");
             self.clear_events();
-            self.process_cmd(e);
+            let _ = self.process_cmd(e);
         } else {
             debug_assert!(*e == Command::Wait, "Giving inputs outside player's turn");
         }
@@ -59,68 +153,333 @@ impl Incremental for World {
 }
 
 impl World {
-    fn process_cmd(&mut self, cmd: &Command) -> ActionOutcome {
+    /// Check whether the player could perform a command, without performing it.
+    ///
+    /// Actions may still fail for reasons that can't be known in advance, but the common reasons
+    /// for rejecting a command are caught here.
+    pub fn check_command(&self, cmd: &Command) -> ActionOutcome {
+        use ActionError::*;
         use Command::*;
+
+        let player = self.player().ok_or(NotInWorld)?;
+        let location = self.location(player).ok_or(NotInWorld)?;
+        // Confused movement may go somewhere else than where it was aimed, so it can't be
+        // rejected before it's tried.
+        let confused = self.has_status(player, Status::Confused);
+
         match cmd {
-            Wait => {
-                Some(())
-            }
-            Pass => {
-                let player = self.player()?;
-                self.idle(player)
-            }
+            Wait | Pass => Ok(()),
             Step(dir) => {
-                let player = self.player()?;
-                self.entity_step(player, *dir)
+                if confused || self.can_step(player, *dir) {
+                    Ok(())
+                } else {
+                    Err(Blocked)
+                }
             }
             Melee(dir) => {
-                let player = self.player()?;
-                self.entity_melee(player, *dir)
+                if !confused {
+                    self.mob_at(location.jump(self, *dir)).ok_or(NoTarget)?;
+                }
+                Ok(())
+            }
+            Shoot(_) => {
+                if self.stats(player).ranged_range > 0 {
+                    Ok(())
+                } else {
+                    Err(NoRangedWeapon)
+                }
+            }
+            Take => {
+                let item = self.item_at(location).ok_or(NothingToTake)?;
+                if self.stack_slot(player, item).is_none() {
+                    self.free_bag_slot(player).ok_or(BagFull)?;
+                }
+                Ok(())
+            }
+            Drop(slot, count) => {
+                let item = self.entity_equipped(player, *slot).ok_or(EmptySlot)?;
+                if *count == 0 {
+                    return Err(NothingToDrop);
+                }
+                if slot.is_equipment_slot() && self.is_cursed(item) {
+                    return Err(Cursed);
+                }
+                Ok(())
+            }
+            Equip(slot) => {
+                let item = self.entity_equipped(player, *slot).ok_or(EmptySlot)?;
+                if slot.is_equipment_slot() {
+                    if self.is_cursed(item) {
+                        return Err(Cursed);
+                    }
+                    self.free_bag_slot(player).ok_or(BagFull)?;
+                } else {
+                    self.free_equip_slot(player, item)?;
+                }
+                Ok(())
+            }
+            UseItem(slot) => {
+                let item = self.entity_equipped(player, *slot).ok_or(EmptySlot)?;
+                match self.item_type(item) {
+                    Some(ItemType::UntargetedUsable(_))
+                    | Some(ItemType::Food(_))
+                    | Some(ItemType::Corpse) => {}
+                    _ => return Err(NotUsable),
+                }
+                if self.uses_left(item) == 0 {
+                    return Err(NoCharges);
+                }
+                Ok(())
+            }
+            Zap(slot, _) => {
+                let item = self.entity_equipped(player, *slot).ok_or(EmptySlot)?;
+                match self.item_type(item) {
+                    Some(ItemType::TargetedUsable(_)) => {}
+                    _ => return Err(NotUsable),
+                }
+                if self.uses_left(item) == 0 {
+                    return Err(NoCharges);
+                }
+                Ok(())
+            }
+            PutIn(slot, container_slot) => {
+                let item = self.entity_equipped(player, *slot).ok_or(EmptySlot)?;
+                let container = self.entity_equipped(player, *container_slot).ok_or(EmptySlot)?;
+                if slot.is_equipment_slot() && self.is_cursed(item) {
+                    return Err(Cursed);
+                }
+                self.check_stash(container, item)
+            }
+            TakeOut(container_slot, idx) => {
+                let container = self.entity_equipped(player, *container_slot).ok_or(EmptySlot)?;
+                if !self.is_container(container) {
+                    return Err(NotAContainer);
+                }
+                let item = *self.contents(container).get(*idx).ok_or(NothingToTake)?;
+                if self.stack_slot(player, item).is_none() {
+                    self.free_bag_slot(player).ok_or(BagFull)?;
+                }
+                Ok(())
             }
+            Cast(slot) | CastAt(slot, _) => {
+                let spell = self.entity_equipped(player, *slot).ok_or(EmptySlot)?;
+                // Spells in the bag haven't been learned yet.
+                if !slot.is_equipment_slot() {
+                    return Err(NotUsable);
+                }
+                let targeted = self.spell(spell).ok_or(NotUsable)?.targeted;
+                if targeted != matches!(cmd, CastAt(..)) {
+                    return Err(NotUsable);
+                }
+                self.check_cast(player, spell)
+            }
+        }
+    }
+
+    fn process_cmd(&mut self, cmd: &Command) -> ActionOutcome {
+        use ActionError::*;
+        use Command::*;
+
+        self.check_command(cmd)?;
+
+        let player = self.player().ok_or(NotInWorld)?;
+        let location = self.location(player).ok_or(NotInWorld)?;
+
+        match cmd {
+            Wait => Ok(()),
+            Pass => self.idle(player),
+            Step(dir) => self.entity_step(player, *dir),
+            Melee(dir) => self.entity_melee(player, *dir),
+            Shoot(dir) => self.entity_shoot(player, *dir),
             Take => {
-                let player = self.player()?;
-                let item = self.item_at(self.location(player)?)?;
+                let item = self.item_at(location).ok_or(NothingToTake)?;
                 self.entity_take(player, item)
             }
-            Drop(slot) => {
-                let player = self.player()?;
-                self.place_entity(self.entity_equipped(player, *slot)?, self.location(player)?);
-                Some(())
+            Drop(slot, count) => {
+                let item = self.entity_equipped(player, *slot).ok_or(EmptySlot)?;
+                let item = self.split_stack(item, *count);
+                self.place_entity(item, location);
+                Ok(())
             }
             Equip(slot) => {
-                let player = self.player()?;
-                let item = self.entity_equipped(player, *slot)?;
+                let item = self.entity_equipped(player, *slot).ok_or(EmptySlot)?;
                 let swap_slot = if slot.is_equipment_slot() {
-                    // Remove equipped.
-                    // TODO: Items that can't be removed because of curses etc. trip here.
-                    self.free_bag_slot(player)?
+                    // Remove equipped, curses were checked above.
+                    self.free_bag_slot(player).ok_or(BagFull)?
                 } else {
                     // Equip from bag.
-                    // TODO: Inability to equip item because stats limits etc. trips here.
                     self.free_equip_slot(player, item)?
                 };
 
                 self.equip_item(item, player, swap_slot);
-                Some(())
+                if swap_slot.is_equipment_slot() && self.is_cursed(item) {
+                    msg!(self, "[One] bind[s] itself to you!")
+                        .subject(item)
+                        .send();
+                }
+                Ok(())
             }
             UseItem(slot) => {
-                let player = self.player()?;
-                let item = self.entity_equipped(player, *slot)?;
-                let location = self.location(player)?;
-                if self.uses_left(item) > 0 {
-                    self.drain_charge(item);
-                    self.cast_spell(location, item, Some(player))
-                } else {
-                    msg!(self, "Nothing happens.").send();
-                    None
+                let item = self.entity_equipped(player, *slot).ok_or(EmptySlot)?;
+                if self.nutrition_value(item).is_some() {
+                    return self.eat(player, item);
                 }
+                self.drain_charge(item);
+                self.cast_spell(location, item, Some(player))
             }
             Zap(slot, dir) => {
-                let player = self.player()?;
-                let item = self.entity_equipped(player, *slot)?;
-                let location = self.location(player)?;
+                let item = self.entity_equipped(player, *slot).ok_or(EmptySlot)?;
+                self.drain_charge(item);
                 self.cast_directed_spell(location, *dir, item, Some(player))
             }
+            PutIn(slot, container_slot) => {
+                let item = self.entity_equipped(player, *slot).ok_or(EmptySlot)?;
+                let container = self.entity_equipped(player, *container_slot).ok_or(EmptySlot)?;
+                self.entity_stash(player, item, container)
+            }
+            TakeOut(container_slot, idx) => {
+                let container = self.entity_equipped(player, *container_slot).ok_or(EmptySlot)?;
+                let item = *self.contents(container).get(*idx).ok_or(NothingToTake)?;
+                self.entity_unstash(player, item, container)
+            }
+            Cast(slot) => {
+                let spell = self.entity_equipped(player, *slot).ok_or(EmptySlot)?;
+                self.cast_learned(player, spell, None)
+            }
+            CastAt(slot, dir) => {
+                let spell = self.entity_equipped(player, *slot).ok_or(EmptySlot)?;
+                self.cast_learned(player, spell, Some(*dir))
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{ActionError, Command};
+    use crate::components::Status;
+    use crate::item::Slot;
+    use crate::mutate::Mutate;
+    use crate::query::Query;
+    use crate::terraform::TerrainQuery;
+    use crate::test_util::{
+        give_item, peaceful_world, saw_message, spawn_next_to_player, wait_for_player,
+    };
+    use calx::{Dir6, Incremental};
+
+    #[test]
+    fn test_check_command() {
+        let world = peaceful_world(1);
+        let player = world.player().unwrap();
+
+        assert_eq!(world.check_command(&Command::Pass), Ok(()));
+        assert_eq!(
+            world.check_command(&Command::Drop(Slot::InventoryZ, 1)),
+            Err(ActionError::EmptySlot)
+        );
+        assert_eq!(
+            world.check_command(&Command::UseItem(Slot::InventoryZ)),
+            Err(ActionError::EmptySlot)
+        );
+        if world.stats(player).ranged_range == 0 {
+            assert_eq!(
+                world.check_command(&Command::Shoot(calx::Dir6::North)),
+                Err(ActionError::NoRangedWeapon)
+            );
+        }
+        if world.item_at(world.location(player).unwrap()).is_none() {
+            assert_eq!(
+                world.check_command(&Command::Take),
+                Err(ActionError::NothingToTake)
+            );
+        }
+    }
+
+    #[test]
+    fn test_shoot() {
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+        let (dreg, dir) = spawn_next_to_player(&mut world, "dreg");
+        world.ecs_mut().stats[dreg].base.power = 100;
+        world.rebuild_stats(dreg);
+
+        // Can't shoot without a ranged weapon, and trying doesn't use up the turn.
+        assert_eq!(world.check_command(&Command::Shoot(dir)), Err(ActionError::NoRangedWeapon));
+        world.update(&Command::Shoot(dir));
+        assert!(world.player_can_act());
+        assert_eq!(world.ecs().health[dreg].wounds, 0);
+
+        let slot = give_item(&mut world, "bow");
+        world.update(&Command::Equip(slot));
+        let bow = world.entity_equipped(player, Slot::Ranged);
+        assert!(bow.is_some());
+        assert!(world.stats(player).ranged_range > 0);
+
+        // Make sure the shot hits with maximum damage.
+        wait_for_player(&mut world);
+        world.ecs_mut().stats[player].base.attack = 100;
+        world.rebuild_stats(player);
+        assert_eq!(world.check_command(&Command::Shoot(dir)), Ok(()));
+        world.update(&Command::Shoot(dir));
+        let damage = 4 * world.stats(player).ranged_power;
+        assert_eq!(world.ecs().health[dreg].wounds, damage);
+        assert!(saw_message(&world, &format!("You shoot the dreg for {}.", damage)));
+    }
+
+    #[test]
+    fn test_confused_step() {
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+
+        // Find a spot next to a wall.
+        let sector = world.location(player).unwrap().sector();
+        let (loc, dir) = sector
+            .iter()
+            .filter(|&loc| !world.terrain(loc).blocks_walk() && world.mob_at(loc).is_none())
+            .find_map(|loc| {
+                Dir6::iter()
+                    .find(|&&d| world.terrain(loc.jump(&world, d)).is_wall())
+                    .map(|&d| (loc, d))
+            })
+            .unwrap();
+        world.place_entity(player, loc);
+        assert_eq!(world.check_command(&Command::Step(dir)), Err(ActionError::Blocked));
+
+        // Confused walking into a wall may still stumble somewhere else.
+        world.gain_status(player, Status::Confused, 1000);
+        assert_eq!(world.check_command(&Command::Step(dir)), Ok(()));
+        for _ in 0..100 {
+            wait_for_player(&mut world);
+            world.place_entity(player, loc);
+            world.update(&Command::Step(dir));
+            if world.location(player) != Some(loc) {
+                break;
+            }
+        }
+        assert_ne!(world.location(player), Some(loc));
+    }
+
+    #[test]
+    fn test_wand_charges() {
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+        let slot = give_item(&mut world, "wand of confusion");
+        let wand = world.entity_equipped(player, slot).unwrap();
+        let charges = world.uses_left(wand);
+        assert!(charges > 1);
+
+        let (_, dir) = spawn_next_to_player(&mut world, "hireling");
+        for _ in 0..charges {
+            wait_for_player(&mut world);
+            world.update(&Command::Zap(slot, dir));
+        }
+        assert_eq!(world.uses_left(wand), 0);
+
+        // Empty wands stick around but don't work.
+        wait_for_player(&mut world);
+        assert_eq!(world.entity_equipped(player, slot), Some(wand));
+        assert_eq!(world.check_command(&Command::Zap(slot, dir)), Err(ActionError::NoCharges));
+        world.update(&Command::Zap(slot, dir));
+        assert!(world.player_can_act());
+    }
+}
@@ -1,4 +1,5 @@
-use crate::item::ItemType;
+use crate::effect::{Aura, OnHit};
+use crate::item::{Affix, ItemType};
 use crate::location::Location;
 use crate::location_set::LocationSet;
 use crate::stats::Stats;
@@ -6,6 +7,7 @@ use crate::FovStatus;
 use calx_ecs::Entity;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::fmt;
 
 /// The visual representation for an entity
 ///
@@ -22,20 +24,53 @@ pub enum Icon {
     Ooze,
     Efreet,
     Serpent,
+    Hireling,
 
     Sword,
+    Bow,
+    Crossbow,
     Helmet,
     Armor,
     Wand1,
     Wand2,
+    Wand3,
+    Wand4,
+    Wand5,
+    Wand6,
     Scroll1,
+    Scroll2,
+    Scroll3,
+    Scroll4,
+    Scroll5,
+    Potion1,
+    Potion2,
+    Potion3,
+    Potion4,
+    Potion5,
+    Corpse,
+    Orb,
+    Gold,
+    Ring,
+    Amulet,
+    Lantern,
+    Bag,
+    Box,
+    Chest,
+    Ration,
+    Apple,
+    Spellbook,
 }
 
 /// Entity name and appearance.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Desc {
+    /// Base name, also used to spawn the entity.
     pub name: String,
     pub icon: Icon,
+    /// Words shown before the base name, like an item's enchantment.
+    pub prefix: String,
+    /// Words shown after the base name, like an item's affix.
+    pub suffix: String,
 }
 
 /// Entity animation state.
@@ -63,8 +98,22 @@ impl Desc {
         Desc {
             name: name.to_string(),
             icon,
+            prefix: String::new(),
+            suffix: String::new(),
         }
     }
+
+    /// Return the base name with prefix and suffix.
+    pub fn full_name(&self) -> String {
+        let mut ret = self.name.clone();
+        if !self.prefix.is_empty() {
+            ret = format!("{} {}", self.prefix, ret);
+        }
+        if !self.suffix.is_empty() {
+            ret = format!("{} {}", ret, self.suffix);
+        }
+        ret
+    }
 }
 
 /// Map field-of-view and remembered terrain.
@@ -91,6 +140,10 @@ pub struct Brain {
     pub state: BrainState,
     pub alignment: Alignment,
     pub shout: ShoutType,
+    /// Percentage of health the mob can lose before it tries to run away.
+    ///
+    /// Mobs with morale 100 or more fight to the death.
+    pub morale: u32,
 }
 
 impl Brain {
@@ -117,6 +170,7 @@ impl Default for Brain {
             state: BrainState::Asleep,
             alignment: Alignment::Evil,
             shout: ShoutType::Silent,
+            morale: 100,
         }
     }
 }
@@ -127,8 +181,14 @@ pub enum BrainState {
     /// AI mob is inactive, but can be startled into action by noise or
     /// motion.
     Asleep,
+    /// AI mob heard something and is going to see what made the noise.
+    Investigating(Location),
     /// AI mob is looking for a fight.
     Hunting(Entity),
+    /// AI mob is too hurt to fight and is running away from the entity.
+    Fleeing(Entity),
+    /// AI mob is keeping close to a friendly leader entity.
+    Following(Entity),
     /// Mob is under player control.
     PlayerControl,
 }
@@ -183,6 +243,28 @@ pub struct Item {
     pub item_type: ItemType,
     /// How many uses a wand or similar has left.
     pub charges: u32,
+    /// Number of identical items in the stack.
+    pub quantity: u32,
+    /// Cursed items can't be taken off once equipped.
+    pub cursed: bool,
+    /// What it takes to equip the item.
+    pub requirements: Requirements,
+    /// Bonus or penalty rolled when the item was generated.
+    pub enchantment: i32,
+    pub affix: Option<Affix>,
+    /// Effects applied to whatever the wielder hits in melee.
+    pub on_hit: Vec<OnHit>,
+    /// Passive effects on the wearer while equipped.
+    pub auras: Vec<Aura>,
+}
+
+/// Conditions a creature must meet to equip an item.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Requirements {
+    /// Minimum power of the wielder.
+    pub min_power: i32,
+    /// Whether the wielder needs the Hands intrinsic.
+    pub hands: bool,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize)]
@@ -196,14 +278,109 @@ pub enum Status {
     Slowed,
     /// Moves 1/3 faster than usual, stacks with Quick intrinsic.
     Hasted,
-    /// Creature is delayed.
-    ///
-    /// This gets jumped up every time after the creature acted.
-    Delayed,
+    /// Takes damage over time.
+    Poisoned,
+    /// Heals over time.
+    Regenerating,
+    /// Magical sleep, can't act until it wears off or the creature gets hurt.
+    Asleep,
+    /// Can't act until it wears off.
+    Paralyzed,
+    /// Can't be picked as a target by hostile creatures.
+    Invisible,
+    /// Takes fire damage over time.
+    Burning,
+    /// Can see invisible creatures.
+    SeeInvisible,
 }
 
 pub type Statuses = BTreeMap<Status, u32>;
 
+/// Turn scheduling state for creatures that take actions.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Schedule {
+    /// World tick on which the creature gets to act next.
+    pub next_action: u64,
+}
+
+/// How well fed a creature is.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Nutrition {
+    /// Food left in the stomach, measured in actions.
+    pub satiation: u32,
+}
+
+impl Default for Nutrition {
+    fn default() -> Nutrition { Nutrition { satiation: 2000 } }
+}
+
+impl Nutrition {
+    /// Most food the stomach can hold.
+    pub const MAX: u32 = 3000;
+
+    pub fn hunger(&self) -> Hunger {
+        match self.satiation {
+            0..=99 => Hunger::Fainting,
+            100..=299 => Hunger::Weak,
+            300..=799 => Hunger::Hungry,
+            _ => Hunger::Satiated,
+        }
+    }
+}
+
+/// Stages of hunger, from well fed to starving.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub enum Hunger {
+    Satiated,
+    Hungry,
+    /// Too weak to regenerate.
+    Weak,
+    /// Passing out now and then, starving once the stomach is completely empty.
+    Fainting,
+}
+
+impl fmt::Display for Hunger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Hunger::Satiated => "Satiated",
+            Hunger::Hungry => "Hungry",
+            Hunger::Weak => "Weak",
+            Hunger::Fainting => "Fainting",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Experience gained from defeating enemies.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Experience {
+    pub xp: u32,
+    pub level: u32,
+}
+
+impl Default for Experience {
+    fn default() -> Experience { Experience { xp: 0, level: 1 } }
+}
+
+impl Experience {
+    /// Return the total experience needed to reach the next level.
+    pub fn next_level_xp(&self) -> u32 { 20 * self.level * self.level }
+}
+
+/// Mana available for casting spells.
+///
+/// The size of the pool comes from the mana stat.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Mana {
+    pub points: i32,
+}
+
+/// Time when a spell can be cast again.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Cooldown {
+    pub ready_at: u64,
+}
+
 /// Stats component in the ECS that supports caching applied modifiers for efficiency.
 #[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct StatsComponent {
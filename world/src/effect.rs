@@ -1,5 +1,9 @@
+use crate::components::Status;
+use crate::stats::Intrinsic;
+use serde_derive::{Deserialize, Serialize};
+
 /// Game system effects on entities.
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Effect {
     /// Heal an amount of damage.
     Heal(u32),
@@ -7,16 +11,68 @@ pub enum Effect {
     Hit { amount: u32, damage: Damage },
     /// Cause erratic behavior for a time.
     Confuse,
+    /// Apply a temporary status for a number of frames.
+    Status(Status, u32),
     /// Target mob learns current surroundings.
     ///
     /// Probably only does anything for player.
     MagicMap,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Damage {
     Physical,
     Fire,
     Electricity,
     Cold,
+    Poison,
+    /// Hunger pangs of an empty stomach.
+    Starvation,
+}
+
+impl Damage {
+    /// Intrinsic that halves damage of this type.
+    pub fn resistance(self) -> Option<Intrinsic> {
+        match self {
+            Damage::Physical => None,
+            Damage::Fire => Some(Intrinsic::ResistFire),
+            Damage::Electricity => Some(Intrinsic::ResistElectricity),
+            Damage::Cold => Some(Intrinsic::ResistCold),
+            Damage::Poison | Damage::Starvation => None,
+        }
+    }
+
+    /// Intrinsic that doubles damage of this type.
+    pub fn vulnerability(self) -> Option<Intrinsic> {
+        match self {
+            Damage::Physical => None,
+            Damage::Fire => Some(Intrinsic::VulnerableFire),
+            Damage::Electricity => Some(Intrinsic::VulnerableElectricity),
+            Damage::Cold => Some(Intrinsic::VulnerableCold),
+            Damage::Poison | Damage::Starvation => None,
+        }
+    }
+}
+
+/// Effect that a piece of equipment applies to creatures its wielder hits in melee.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct OnHit {
+    pub effect: Effect,
+    /// Probability of the effect triggering on a hit.
+    pub chance: f32,
+}
+
+impl OnHit {
+    pub fn new(effect: Effect, chance: f32) -> OnHit { OnHit { effect, chance } }
+}
+
+/// Passive effect that a piece of equipment has on its wearer.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Aura {
+    /// Slowly heal wounds.
+    Regeneration,
+    /// See invisible creatures.
+    SeeInvisible,
+    /// Extend sight range in the dark dungeon.
+    Light(u32),
 }
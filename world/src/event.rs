@@ -11,4 +11,7 @@ pub enum Event {
 
     /// Damage dealt to an entity. Use negative values to show healing.
     Damage { entity: Entity, amount: i32 },
+
+    /// Entity has gained enough experience to reach a new level.
+    LevelUp { entity: Entity, level: u32 },
 }
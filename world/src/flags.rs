@@ -1,6 +1,7 @@
 use crate::location::Location;
 use calx_ecs::Entity;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 
 #[derive(Serialize, Deserialize)]
 pub struct Flags {
@@ -10,6 +11,10 @@ pub struct Flags {
     /// Store the player entity here for fast access.
     pub player: Option<Entity>,
     pub depth: i32,
+    /// Gold collected by the player.
+    pub gold: u32,
+    /// True names of the item kinds the player has identified.
+    pub identified: BTreeSet<String>,
 }
 
 impl Flags {
@@ -20,6 +25,8 @@ impl Flags {
             player_acted: false,
             player: None,
             depth: 0,
+            gold: 0,
+            identified: BTreeSet::new(),
         }
     }
 }
@@ -14,6 +14,7 @@ pub struct Noun {
     is_you: bool,
     name: String,
     pronoun: Pronoun,
+    count: u32,
 }
 
 impl Noun {
@@ -22,6 +23,7 @@ impl Noun {
             is_you: false,
             pronoun: Pronoun::It,
             name,
+            count: 1,
         }
     }
 
@@ -35,15 +37,32 @@ impl Noun {
         self
     }
 
+    /// Set the number of things the noun stands for.
+    pub fn count(mut self, count: u32) -> Noun {
+        self.count = count;
+        self
+    }
+
     pub fn is_proper_noun(&self) -> bool { is_capitalized(&self.name) }
 
+    pub fn is_plural(&self) -> bool { self.count > 1 }
+
+    /// Name with the count prepended for plurals, "potion" or "3 potions".
+    pub fn counted_name(&self) -> String {
+        if self.is_plural() {
+            format!("{} {}", self.count, pluralize(&self.name))
+        } else {
+            self.name.clone()
+        }
+    }
+
     pub fn the_name(&self) -> String {
         if self.is_you {
             "you".to_string()
         } else if self.is_proper_noun() {
             self.name.to_string()
         } else {
-            format!("the {}", self.name)
+            format!("the {}", self.counted_name())
         }
     }
 
@@ -52,6 +71,8 @@ impl Noun {
             "you".to_string()
         } else if self.is_proper_noun() {
             self.name.to_string()
+        } else if self.is_plural() {
+            self.counted_name()
         } else {
             // TODO: Add look-up table of irregular words ('honor', 'unit') as they show up in game
             // text.
@@ -67,6 +88,8 @@ impl Noun {
     pub fn they(&self) -> String {
         if self.is_you {
             "you".to_string()
+        } else if self.is_plural() {
+            "they".to_string()
         } else {
             match self.pronoun {
                 Pronoun::He => "he".to_string(),
@@ -80,6 +103,8 @@ impl Noun {
     pub fn them(&self) -> String {
         if self.is_you {
             "you".to_string()
+        } else if self.is_plural() {
+            "them".to_string()
         } else {
             match self.pronoun {
                 Pronoun::He => "him".to_string(),
@@ -96,13 +121,15 @@ impl Noun {
         } else if self.is_proper_noun() {
             format!("{}'s", self.name.to_string())
         } else {
-            format!("the {}'s", self.name)
+            format!("the {}'s", self.counted_name())
         }
     }
 
     pub fn reflexive(&self) -> String {
         if self.is_you {
             "yourself".to_string()
+        } else if self.is_plural() {
+            "themselves".to_string()
         } else {
             match self.pronoun {
                 Pronoun::He => "himself".to_string(),
@@ -159,6 +186,11 @@ impl SubjectTemplater {
             used_pronoun_they: false,
         }
     }
+
+    /// Does the verb take the plural form, "you hit", "they hit", "the 3 bats hit"?
+    fn plural_verb(&self) -> bool {
+        self.subject.is_you || self.subject.is_plural() || self.used_pronoun_they
+    }
 }
 
 impl Templater for SubjectTemplater {
@@ -185,7 +217,7 @@ impl Templater for SubjectTemplater {
             // All of these are assummed to apply to subject.
             // hit/hits
             "s" => {
-                if self.subject.is_you || self.used_pronoun_they {
+                if self.plural_verb() {
                     "".to_string()
                 } else {
                     "s".to_string()
@@ -193,7 +225,7 @@ impl Templater for SubjectTemplater {
             }
             // slash/slashes
             "es" => {
-                if self.subject.is_you || self.used_pronoun_they {
+                if self.plural_verb() {
                     "".to_string()
                 } else {
                     "es".to_string()
@@ -201,21 +233,21 @@ impl Templater for SubjectTemplater {
             }
             // parry/parries
             "ies" => {
-                if self.subject.is_you || self.used_pronoun_they {
+                if self.plural_verb() {
                     "y".to_string()
                 } else {
                     "ies".to_string()
                 }
             }
             "is" | "are" => {
-                if self.subject.is_you || self.used_pronoun_they {
+                if self.plural_verb() {
                     "are".to_string()
                 } else {
                     "is".to_string()
                 }
             }
             "has" | "have" => {
-                if self.subject.is_you || self.used_pronoun_they {
+                if self.plural_verb() {
                     "have".to_string()
                 } else {
                     "has".to_string()
@@ -275,6 +307,31 @@ pub fn capitalize(word: &str) -> String {
     cap.chain(iter).collect()
 }
 
+/// Return the plural form of a noun phrase.
+///
+/// The head noun is the word before a qualifier, as in "potions of healing", or the last word of
+/// the phrase, as in "fizzy potions".
+pub fn pluralize(phrase: &str) -> String {
+    for qualifier in &[" of ", " labeled "] {
+        if let Some(i) = phrase.find(qualifier) {
+            return format!("{}{}", pluralize(&phrase[..i]), &phrase[i..]);
+        }
+    }
+
+    let (head, word) = match phrase.rfind(' ') {
+        Some(i) => phrase.split_at(i + 1),
+        None => ("", phrase),
+    };
+    let plural = if ["s", "x", "z", "ch", "sh"].iter().any(|&end| word.ends_with(end)) {
+        format!("{}es", word)
+    } else if word.ends_with('y') && !word[..word.len() - 1].ends_with(is_vowel) {
+        format!("{}ies", &word[..word.len() - 1])
+    } else {
+        format!("{}s", word)
+    };
+    format!("{}{}", head, plural)
+}
+
 pub fn is_vowel(c: char) -> bool {
     // If accented chars are used, they need to be added here...
     match c.to_lowercase().next().unwrap_or('\0') {
@@ -454,4 +511,34 @@ mod test {
             assert_eq!(t.format(template), Ok(message.to_string()));
         }
     }
+
+    #[test]
+    fn test_plurals() {
+        use super::pluralize;
+        for &(one, many) in &[
+            ("potion", "potions"),
+            ("potion of healing", "potions of healing"),
+            ("fizzy potion", "fizzy potions"),
+            ("scroll labeled ESKA VORN", "scrolls labeled ESKA VORN"),
+            ("torch", "torches"),
+            ("ruby", "rubies"),
+            ("key", "keys"),
+        ] {
+            assert_eq!(pluralize(one), many);
+        }
+
+        let arrows = Noun::new("arrow".to_string()).count(3);
+        assert_eq!(arrows.a_name(), "3 arrows");
+        let mut t = ObjectTemplater::new(SubjectTemplater::new(make_noun("PLAYER")), arrows);
+        assert_eq!(
+            t.format("[One] pick[s] up [another]."),
+            Ok("You pick up the 3 arrows.".to_string())
+        );
+
+        let mut t = SubjectTemplater::new(Noun::new("potion".to_string()).count(2));
+        assert_eq!(
+            t.format("[One] shatter[s]. [They] [are] gone."),
+            Ok("The 2 potions shatter. They are gone.".to_string())
+        );
+    }
 }
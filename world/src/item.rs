@@ -1,4 +1,11 @@
+use crate::components::Icon;
+use crate::spec;
+use crate::stats::{Intrinsic, Stats};
+use crate::Rng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::slice;
 
 /// Inventory slots.
@@ -91,11 +98,48 @@ pub enum ItemType {
     Boots,
     /// Passive effects when equipped
     Trinket,
-    Spell,
+    /// Learned magic, cast from a spell slot.
+    Spell(Spell),
     UntargetedUsable(MagicEffect),
     TargetedUsable(MagicEffect),
     /// Consumed instantly when stepped on.
     Instant(MagicEffect),
+    /// Remains of a dead creature.
+    Corpse,
+    /// Money, picked up instantly when the player steps on it.
+    Gold,
+    /// Holds up to the given number of other items.
+    Container(u32),
+    /// Restores the given amount of nutrition when eaten.
+    Food(u32),
+}
+
+impl ItemType {
+    /// Return the kind of equipment slot the item goes in.
+    pub fn equip_type(self) -> Option<EquipType> {
+        use self::ItemType::*;
+        match self {
+            MeleeWeapon => Some(EquipType::Melee),
+            RangedWeapon => Some(EquipType::Ranged),
+            Helmet => Some(EquipType::Head),
+            Armor => Some(EquipType::Body),
+            Boots => Some(EquipType::Feet),
+            Spell(_) => Some(EquipType::Spell),
+            Trinket => Some(EquipType::Trinket),
+            _ => None,
+        }
+    }
+}
+
+/// Magic that is cast with mana instead of being used up like a scroll or a wand.
+#[derive(Copy, Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Spell {
+    pub effect: MagicEffect,
+    /// Whether the spell is aimed in a direction.
+    pub targeted: bool,
+    pub mana_cost: i32,
+    /// Frames before the spell can be cast again.
+    pub cooldown: u32,
 }
 
 #[derive(Copy, Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
@@ -104,6 +148,117 @@ pub enum MagicEffect {
     Confuse,
     Lightning,
     Fireball,
+    MagicMap,
+    Poison,
+    Sleep,
+    Paralyze,
+    Invisibility,
+    Regeneration,
+    Identify,
+    RemoveCurse,
+}
+
+/// Kinds of magic items that look alike until the player identifies them.
+#[derive(Copy, Eq, PartialEq, Clone, Debug, Hash)]
+pub enum ItemClass {
+    Potion,
+    Scroll,
+    Wand,
+}
+
+impl ItemClass {
+    /// Return the class of items drawn with the given icon.
+    pub fn from_icon(icon: Icon) -> Option<ItemClass> {
+        use crate::components::Icon::*;
+        match icon {
+            Potion1 | Potion2 | Potion3 | Potion4 | Potion5 => Some(ItemClass::Potion),
+            Scroll1 | Scroll2 | Scroll3 | Scroll4 | Scroll5 => Some(ItemClass::Scroll),
+            Wand1 | Wand2 | Wand3 | Wand4 | Wand5 | Wand6 => Some(ItemClass::Wand),
+            _ => None,
+        }
+    }
+
+    /// Icons that unidentified items of this class are drawn with.
+    pub fn icons(self) -> &'static [Icon] {
+        use crate::components::Icon::*;
+        match self {
+            ItemClass::Potion => &[Potion1, Potion2, Potion3, Potion4, Potion5],
+            ItemClass::Scroll => &[Scroll1, Scroll2, Scroll3, Scroll4, Scroll5],
+            ItemClass::Wand => &[Wand1, Wand2, Wand3, Wand4, Wand5, Wand6],
+        }
+    }
+
+    /// Descriptions that get shuffled among the item kinds of this class.
+    pub fn appearances(self) -> &'static [&'static str] {
+        match self {
+            ItemClass::Potion => &[
+                "murky", "fizzy", "glowing", "bubbling", "smoky", "milky", "amber", "violet",
+                "oily", "cloudy",
+            ],
+            ItemClass::Scroll => &[
+                "OBLOM ZHAR",
+                "VASH ORREK",
+                "TIRMA NUL",
+                "ESKA VORN",
+                "DRUMO LAKK",
+                "PHEN YADDOR",
+                "ULUK SEMAR",
+                "QAZ MIRRIN",
+                "HESTRO VEL",
+                "ANKU TOBRE",
+            ],
+            ItemClass::Wand => &[
+                "oak", "bone", "iron", "glass", "copper", "ivory", "crystal", "ebony", "silver",
+                "willow",
+            ],
+        }
+    }
+
+    /// Turn an appearance into the name shown for an unidentified item.
+    pub fn describe(self, appearance: &str) -> String {
+        match self {
+            ItemClass::Potion => format!("{} potion", appearance),
+            ItemClass::Scroll => format!("scroll labeled {}", appearance),
+            ItemClass::Wand => format!("{} wand", appearance),
+        }
+    }
+}
+
+/// Names and icons of the unidentified magic item kinds in one game, keyed by true name.
+pub type Appearances = HashMap<String, (String, Icon)>;
+
+/// Shuffle the appearances of the magic item kinds for a game.
+///
+/// The RNG is seeded straight from the world seed instead of through a std hasher, whose output
+/// may change between Rust releases, so saved games keep their potion colors.
+pub fn roll_appearances(seed: u32) -> Appearances {
+    let mut ret = HashMap::new();
+    for (i, &class) in [ItemClass::Potion, ItemClass::Scroll, ItemClass::Wand].iter().enumerate() {
+        let mut kinds: Vec<String> = spec::iter_specs()
+            .filter(|s| ItemClass::from_icon(s.icon()) == Some(class))
+            .map(|s| s.name().to_string())
+            .collect();
+        kinds.sort();
+
+        let mut appearances = class.appearances().to_vec();
+        assert!(
+            kinds.len() <= appearances.len(),
+            "More {:?} kinds than appearances for them",
+            class
+        );
+
+        let mut rng = Rng::seed_from_u64((u64::from(seed) << 8) + i as u64);
+        appearances.shuffle(&mut rng);
+        let mut icons = class.icons().to_vec();
+        icons.shuffle(&mut rng);
+
+        for (idx, kind) in kinds.into_iter().enumerate() {
+            // The names tell kinds apart, icons can repeat when there aren't enough of them.
+            let appearance = (class.describe(appearances[idx]), icons[idx % icons.len()]);
+            ret.insert(kind, appearance);
+        }
+    }
+    ret
 }
 
 #[derive(Copy, Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
@@ -116,3 +271,69 @@ pub enum EquipType {
     Spell,
     Trinket,
 }
+
+/// Named bonus that equipment can be generated with.
+#[derive(Copy, Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum Affix {
+    Fire,
+    Venom,
+    Might,
+    Haste,
+    Protection,
+    FireWarding,
+    FrostWarding,
+}
+
+impl Affix {
+    pub fn iter() -> slice::Iter<'static, Affix> {
+        use self::Affix::*;
+        static ALL: [Affix; 7] = [Fire, Venom, Might, Haste, Protection, FireWarding, FrostWarding];
+
+        ALL.iter()
+    }
+
+    /// Return whether the affix can show up on equipment of the given type.
+    pub fn fits(self, equip_type: EquipType) -> bool {
+        use self::Affix::*;
+        match self {
+            Fire | Venom | Might => equip_type == EquipType::Melee,
+            Haste => equip_type == EquipType::Feet || equip_type == EquipType::Trinket,
+            Protection => matches!(
+                equip_type,
+                EquipType::Head | EquipType::Body | EquipType::Feet
+            ),
+            FireWarding | FrostWarding => matches!(
+                equip_type,
+                EquipType::Head | EquipType::Body | EquipType::Trinket
+            ),
+        }
+    }
+
+    /// Return the stat bonus the affix gives.
+    pub fn stats(self) -> Stats {
+        use self::Affix::*;
+        match self {
+            Fire => Stats::new(0, &[Intrinsic::Fiery]),
+            Venom => Stats::new(0, &[Intrinsic::Venomous]),
+            Might => Stats::default().attack(3),
+            Haste => Stats::default().speed(20),
+            Protection => Stats::default().defense(2),
+            FireWarding => Stats::new(0, &[Intrinsic::ResistFire]),
+            FrostWarding => Stats::new(0, &[Intrinsic::ResistCold]),
+        }
+    }
+
+    /// Return the name suffix of items with the affix.
+    pub fn suffix(self) -> &'static str {
+        use self::Affix::*;
+        match self {
+            Fire => "of fire",
+            Venom => "of venom",
+            Might => "of might",
+            Haste => "of haste",
+            Protection => "of protection",
+            FireWarding => "of fire warding",
+            FrostWarding => "of frost warding",
+        }
+    }
+}
@@ -97,10 +97,10 @@ impl<'a, W: mutate::Mutate> MessageFormatter2<'a, W> {
 mod biome;
 
 mod command;
-pub use crate::command::{ActionOutcome, Command};
+pub use crate::command::{ActionError, ActionOutcome, Command};
 
 mod components;
-pub use crate::components::Icon;
+pub use crate::components::{Hunger, Icon};
 
 mod effect;
 
@@ -129,6 +129,8 @@ mod map;
 mod mutate;
 pub use crate::mutate::Mutate;
 
+mod overlay;
+
 mod query;
 pub use crate::query::Query;
 
@@ -142,6 +144,9 @@ pub use crate::terraform::{Terraform, TerrainQuery};
 pub mod terrain;
 pub use crate::terrain::Terrain;
 
+#[cfg(test)]
+mod test_util;
+
 mod vaults;
 
 mod volume;
@@ -48,6 +48,15 @@ impl LocationSet {
         }
     }
 
+    /// Iterate the locations in the set in no particular order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = Location> + 'a {
+        self.chunks.iter().flat_map(|(&index, &bits)| {
+            (0..64)
+                .filter(move |i| bits & (1 << i) != 0)
+                .map(move |i| Location::from_morton((index << 6) + i))
+        })
+    }
+
     pub fn extend<I: Iterator<Item = Location>>(&mut self, iter: I) {
         for i in iter {
             self.insert(i);
@@ -1,12 +1,17 @@
-use crate::command::ActionOutcome;
-use crate::components::{Brain, BrainState, MapMemory, Status};
-use crate::effect::{Damage, Effect};
+use crate::command::{ActionError, ActionOutcome};
+use crate::components::{
+    Alignment, Brain, BrainState, Cooldown, Desc, Experience, Hunger, Icon, Item, Mana, MapMemory,
+    Nutrition, Status,
+};
+use crate::effect::{Aura, Damage, Effect};
 use crate::event::Event;
+use crate::grammar::Noun;
 use crate::item::{ItemType, MagicEffect, Slot};
 use crate::location::Location;
 use crate::mapsave;
 use crate::query::Query;
 use crate::spec;
+use crate::stats::{Intrinsic, Stats};
 use crate::terraform::Terraform;
 use crate::volume::Volume;
 use crate::world::{Ecs, Loadout};
@@ -17,6 +22,22 @@ use calx_ecs::Entity;
 use rand::seq::SliceRandom;
 use rand::Rng;
 
+/// Loudness of an alerted mob's shout.
+const SHOUT_NOISE: u32 = 12;
+/// Loudness of a door being opened.
+const DOOR_NOISE: u32 = 4;
+/// Loudness of a creature getting hit.
+const COMBAT_NOISE: u32 = 6;
+/// Loudness of a magical explosion.
+const EXPLOSION_NOISE: u32 = 15;
+
+/// Cost of a regular action like taking a step, as percentage of a normal turn.
+const STANDARD_ACTION: u32 = 100;
+/// A melee swing takes longer than a step.
+const MELEE_ACTION: u32 = 120;
+/// Picking up an item is quick.
+const QUICK_ACTION: u32 = 50;
+
 /// World-mutating methods that are not exposed outside the crate.
 pub trait Mutate: Query + Terraform + Sized {
     /// Advance world state after player input has been received.
@@ -36,8 +57,16 @@ pub trait Mutate: Query + Terraform + Sized {
 
     fn equip_item(&mut self, e: Entity, parent: Entity, slot: Slot);
 
+    /// Put an entity inside a container entity.
+    fn place_in(&mut self, e: Entity, container: Entity);
+
     fn set_player(&mut self, player: Option<Entity>);
 
+    fn add_gold(&mut self, amount: u32);
+
+    /// Mark the item kind with the given true name as identified.
+    fn identify_kind(&mut self, name: String);
+
     /// Mark an entity as dead, but don't remove it from the system yet.
     fn kill_entity(&mut self, e: Entity);
 
@@ -69,7 +98,7 @@ pub trait Mutate: Query + Terraform + Sized {
             if !self.is_npc(npc) {
                 continue;
             }
-            if self.ticks_this_frame(npc) {
+            if self.ticks_this_frame(npc) && !self.is_incapacitated(npc) {
                 self.run_ai_for(npc)
             }
         }
@@ -77,55 +106,202 @@ pub trait Mutate: Query + Terraform + Sized {
 
     /// Run AI for one non-player-controlled creature.
     fn run_ai_for(&mut self, npc: Entity) {
-        const WAKEUP_DISTANCE: i32 = 5;
-
         use crate::components::BrainState::*;
         let brain_state = self.brain_state(npc).expect("Running AI for non-mob");
         match brain_state {
             Asleep => {
-                // XXX: Only treat player mob as potential hostile.
-                // Can't model area conflict effects yet.
-                if let (Some(loc), Some(player), Some(player_loc)) = (
-                    self.location(npc),
-                    self.player(),
-                    self.player().map(|p| self.location(p)).unwrap_or(None),
-                ) {
-                    if self.player_sees(loc) {
-                        // Okay, tricky spot. Player might be seeing mob across a portal, in
-                        // which case we can't do naive distance check.
-                        // This could have a helper method that finds chart distance to self in
-                        // player's map memory.
-                        //
-                        // For now, let's just go with mobs past portals not waking up to
-                        // player.
-
-                        if loc.metric_distance(player_loc) <= WAKEUP_DISTANCE {
-                            self.designate_enemy(npc, player);
+                if !self.look_for_enemies(npc) {
+                    self.look_for_leader(npc);
+                }
+            }
+            Investigating(origin) => {
+                if self.look_for_enemies(npc) {
+                    return;
+                }
+
+                let my_loc = if let Some(loc) = self.location(npc) {
+                    loc
+                } else {
+                    return;
+                };
+
+                let move_dir = if my_loc == origin {
+                    None
+                } else {
+                    self.pathing_dir_towards(npc, origin)
+                };
+
+                if let Some(move_dir) = move_dir {
+                    let _ = self.entity_step(npc, move_dir);
+                } else {
+                    // Found nothing or can't get there, lose interest.
+                    self.calm_down(npc);
+                }
+            }
+            Hunting(target) => {
+                if !self.is_alive(target) || self.is_hidden_from(npc, target) {
+                    // Target is gone, look for a new one.
+                    self.calm_down(npc);
+                    self.look_for_enemies(npc);
+                    return;
+                }
+
+                if self.is_demoralized(npc) {
+                    let flee_dir = self
+                        .location(target)
+                        .and_then(|threat_loc| self.flee_dir(npc, threat_loc));
+                    if let (Some(dir), Some(loc)) = (flee_dir, self.location(npc)) {
+                        self.set_brain_state(npc, Fleeing(target));
+                        if self.player_sees(loc) {
+                            msg!(self, "[One] turn[s] to flee.").subject(npc).send();
                         }
+                        let _ = self.entity_step(npc, dir);
+                        return;
                     }
                 }
+
+                self.attack_or_approach(npc, target);
             }
-            Hunting(target) => {
-                if let (Some(my_loc), Some(target_loc)) =
-                    (self.location(npc), self.location(target))
+            Fleeing(threat) => {
+                self.flee_from(npc, threat);
+            }
+            Following(leader) => {
+                const FOLLOW_DISTANCE: i32 = 2;
+
+                if self.look_for_enemies(npc) {
+                    return;
+                }
+
+                if let (Some(my_loc), Some(leader_loc)) =
+                    (self.location(npc), self.location(leader))
                 {
-                    if my_loc.metric_distance(target_loc) == 1 {
-                        let _ = self.entity_melee(npc, my_loc.dir6_towards(target_loc).unwrap());
-                    } else if let Some(move_dir) = self.pathing_dir_towards(npc, target_loc) {
-                        let _ = self.entity_step(npc, move_dir);
+                    if my_loc.metric_distance(leader_loc) > FOLLOW_DISTANCE {
+                        if let Some(move_dir) = self.pathing_dir_towards(npc, leader_loc) {
+                            let _ = self.entity_step(npc, move_dir);
+                        }
                     }
+                } else if !self.is_alive(leader) {
+                    self.set_brain_state(npc, Asleep);
                 }
             }
             PlayerControl => {}
         }
     }
 
+    fn set_brain_state(&mut self, e: Entity, state: BrainState) {
+        if let Some(brain) = self.ecs_mut().brain.get_mut(e) {
+            brain.state = state;
+        }
+    }
+
+    /// Melee the target if it's adjacent, otherwise move towards it.
+    fn attack_or_approach(&mut self, npc: Entity, target: Entity) {
+        if let (Some(my_loc), Some(target_loc)) = (self.location(npc), self.location(target)) {
+            if my_loc.metric_distance(target_loc) == 1 {
+                let _ = self.entity_melee(npc, my_loc.dir6_towards(target_loc).unwrap());
+            } else if let Some(move_dir) = self.pathing_dir_towards(npc, target_loc) {
+                let _ = self.entity_step(npc, move_dir);
+            }
+        }
+    }
+
+    /// Run away from a threat.
+    ///
+    /// A mob that gets far enough stops to recover, and goes back to hunting once it's fully
+    /// healed. A cornered mob turns to fight.
+    fn flee_from(&mut self, npc: Entity, threat: Entity) {
+        const SAFE_DISTANCE: i32 = 8;
+
+        let (my_loc, threat_loc) = match (self.location(npc), self.location(threat)) {
+            (Some(my_loc), Some(threat_loc)) => (my_loc, threat_loc),
+            _ => {
+                // Threat is gone.
+                self.calm_down(npc);
+                return;
+            }
+        };
+
+        let is_safe = match my_loc.distance_from(threat_loc) {
+            Some(d) => d > SAFE_DISTANCE,
+            // Threat is on another level.
+            None => true,
+        };
+
+        if is_safe {
+            // Safe for now, catch breath.
+            let _ = self.idle(npc);
+            if self.hp(npc) >= self.max_hp(npc) {
+                self.set_brain_state(npc, BrainState::Hunting(threat));
+            }
+        } else if let Some(dir) = self.flee_dir(npc, threat_loc) {
+            let _ = self.entity_step(npc, dir);
+        } else {
+            self.set_brain_state(npc, BrainState::Hunting(threat));
+            if self.player_sees(my_loc) {
+                msg!(self, "[One] turn[s] to fight.").subject(npc).send();
+            }
+            self.attack_or_approach(npc, threat);
+        }
+    }
+
+    /// Make an idle mob check for enemies to go after.
+    ///
+    /// The nearest visible hostile mob becomes the target. Return true if an enemy was found.
+    fn look_for_enemies(&mut self, npc: Entity) -> bool {
+        const WAKEUP_DISTANCE: u32 = 5;
+
+        if let Some(enemy) = self.nearest_hostile(npc, WAKEUP_DISTANCE) {
+            self.designate_enemy(npc, enemy);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Make an idle friendly mob start following the player if it can see the player.
+    ///
+    /// Return true if the mob started following.
+    fn look_for_leader(&mut self, npc: Entity) -> bool {
+        const JOIN_DISTANCE: u32 = 5;
+
+        if self.alignment(npc) != Some(Alignment::Good) {
+            return false;
+        }
+
+        if let Some(player) = self.player() {
+            if self.mobs_in_view(npc, JOIN_DISTANCE).contains(&player) {
+                self.set_brain_state(npc, BrainState::Following(player));
+                msg!(self, "[One] join[s] [another].")
+                    .subject(npc)
+                    .object(player)
+                    .send();
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Return a mob to its idle state after it's done with its business.
+    ///
+    /// Allies go back to following the player, everyone else goes back to sleep.
+    fn calm_down(&mut self, npc: Entity) {
+        match (self.alignment(npc), self.player()) {
+            (Some(Alignment::Good), Some(player)) => {
+                self.set_brain_state(npc, BrainState::Following(player))
+            }
+            _ => self.set_brain_state(npc, BrainState::Asleep),
+        }
+    }
+
     /// End move for entity.
     ///
-    /// Applies delay.
-    fn end_turn(&mut self, e: Entity) {
-        let delay = self.action_delay(e);
-        self.gain_status(e, Status::Delayed, delay);
+    /// Schedules the entity's next action based on the cost of the action it just did.
+    fn end_turn(&mut self, e: Entity, cost: u32) {
+        let next_action = self.get_tick() + u64::from(self.action_delay(e, cost));
+        if let Some(schedule) = self.ecs_mut().schedule.get_mut(e) {
+            schedule.next_action = next_action;
+        }
+        self.consume_nutrition(e);
     }
 
     fn notify_attacked_by(&mut self, victim: Entity, attacker: Entity) {
@@ -137,20 +313,29 @@ pub trait Mutate: Query + Terraform + Sized {
     fn designate_enemy(&mut self, e: Entity, target: Entity) {
         // TODO: Probably want this logic to be more complex eventually.
         if self.is_npc(e) {
-            if self.brain_state(e) == Some(BrainState::Asleep) {
-                self.shout(e);
-            }
-            if let Some(brain) = self.ecs_mut().brain.get_mut(e) {
-                brain.state = BrainState::Hunting(target);
+            match self.brain_state(e) {
+                Some(BrainState::Asleep) | Some(BrainState::Investigating(_)) => self.shout(e),
+                Some(BrainState::Fleeing(_)) => {
+                    // Keep running, but from the new enemy.
+                    self.set_brain_state(e, BrainState::Fleeing(target));
+                    return;
+                }
+                _ => {}
             }
+            self.set_brain_state(e, BrainState::Hunting(target));
         }
     }
 
     /// Make a mob shout according to its type.
     fn shout(&mut self, e: Entity) {
-        // TODO: Create noise, wake up other nearby monsters.
         use crate::components::ShoutType;
         if let Some(shout) = self.ecs().brain.get(e).map(|b| b.shout) {
+            if shout != ShoutType::Silent {
+                if let Some(loc) = self.location(e) {
+                    self.make_noise(loc, SHOUT_NOISE);
+                }
+            }
+
             match shout {
                 ShoutType::Shout => {
                     msg!(self, "[One] shout[s] angrily.").subject(e).send();
@@ -172,6 +357,29 @@ pub trait Mutate: Query + Terraform + Sized {
         }
     }
 
+    /// Make a noise that wakes up sleeping mobs within hearing distance.
+    ///
+    /// Mobs that hear the noise and aren't already busy fighting come to investigate its source.
+    fn make_noise(&mut self, origin: Location, loudness: u32) {
+        let listeners: Vec<Entity> = Volume::noise(self, origin, loudness)
+            .0
+            .into_iter()
+            .flat_map(|loc| self.entities_at(loc))
+            .filter(|&e| self.is_npc(e))
+            .collect();
+
+        for e in listeners {
+            if let Some(brain) = self.ecs_mut().brain.get_mut(e) {
+                match brain.state {
+                    BrainState::Asleep | BrainState::Investigating(_) => {
+                        brain.state = BrainState::Investigating(origin);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
     /// Remove destroyed entities from system
     fn clean_dead(&mut self) {
         let kill_list: Vec<Entity> = self
@@ -200,7 +408,7 @@ pub trait Mutate: Query + Terraform + Sized {
 
     fn entity_step(&mut self, e: Entity, dir: Dir6) -> ActionOutcome {
         if self.confused_move(e) {
-            Some(())
+            Ok(())
         } else {
             self.really_step(e, dir)
         }
@@ -208,20 +416,28 @@ pub trait Mutate: Query + Terraform + Sized {
 
     fn entity_melee(&mut self, e: Entity, dir: Dir6) -> ActionOutcome {
         if self.confused_move(e) {
-            Some(())
+            Ok(())
         } else {
             self.really_melee(e, dir)
         }
     }
 
+    fn entity_shoot(&mut self, e: Entity, dir: Dir6) -> ActionOutcome {
+        if self.confused_move(e) {
+            Ok(())
+        } else {
+            self.really_shoot(e, dir)
+        }
+    }
+
     fn entity_take(&mut self, e: Entity, item: Entity) -> ActionOutcome {
         // Only mobs can take items.
         if !self.is_mob(e) {
-            return None;
+            return Err(ActionError::CannotTake);
         }
 
         if !self.is_item(item) {
-            return None;
+            return Err(ActionError::CannotTake);
         }
 
         // Somehow trying to pick up something we're inside of. Pls don't break the universe.
@@ -229,21 +445,79 @@ pub trait Mutate: Query + Terraform + Sized {
             panic!("Trying to pick up an entity you are inside of. This shouldn't happen");
         }
 
-        if let Some(slot) = self.free_bag_slot(e) {
-            self.equip_item(item, e, slot);
+        if let Some(slot) = self.stack_slot(e, item).or_else(|| self.free_bag_slot(e)) {
             if self.is_player(e) {
                 msg!(self, "[One] pick[s] up [another].")
                     .subject(e)
                     .object(item)
                     .send();
             }
+            match self.entity_equipped(e, slot) {
+                Some(stack) => self.merge_stack(stack, item),
+                None => self.equip_item(item, e, slot),
+            }
 
-            self.end_turn(e);
-            Some(())
+            self.end_turn(e, QUICK_ACTION);
+            Ok(())
         } else {
             // No more inventory space
-            None
+            Err(ActionError::BagFull)
+        }
+    }
+
+    /// Put an item inside a container.
+    fn entity_stash(&mut self, e: Entity, item: Entity, container: Entity) -> ActionOutcome {
+        self.check_stash(container, item)?;
+
+        if self.is_player(e) {
+            let container_name = self.noun(container).the_name();
+            msg!(self, "[One] put[s] [another] in {}.", container_name)
+                .subject(e)
+                .object(item)
+                .send();
+        }
+        match self.container_stack(container, item) {
+            Some(stack) => self.merge_stack(stack, item),
+            None => self.place_in(item, container),
         }
+        // The item might have come from an equipment slot.
+        self.rebuild_stats(e);
+
+        self.end_turn(e, QUICK_ACTION);
+        Ok(())
+    }
+
+    /// Take an item out of a container into the entity's inventory.
+    fn entity_unstash(&mut self, e: Entity, item: Entity, container: Entity) -> ActionOutcome {
+        let slot = self
+            .stack_slot(e, item)
+            .or_else(|| self.free_bag_slot(e))
+            .ok_or(ActionError::BagFull)?;
+
+        if self.is_player(e) {
+            let container_name = self.noun(container).the_name();
+            msg!(self, "[One] take[s] [another] out of {}.", container_name)
+                .subject(e)
+                .object(item)
+                .send();
+        }
+        match self.entity_equipped(e, slot) {
+            Some(stack) => self.merge_stack(stack, item),
+            None => self.equip_item(item, e, slot),
+        }
+
+        self.end_turn(e, QUICK_ACTION);
+        Ok(())
+    }
+
+    /// Destroy an item, spilling anything inside it on the ground.
+    fn destroy_item(&mut self, item: Entity) {
+        if let Some(loc) = self.location(item) {
+            for content in self.contents(item) {
+                self.place_entity(content, loc);
+            }
+        }
+        self.kill_entity(item);
     }
 
     /// Cast an undirected spell
@@ -253,41 +527,16 @@ pub trait Mutate: Query + Terraform + Sized {
         effect: Entity,
         caster: Option<Entity>,
     ) -> ActionOutcome {
-        if let ItemType::UntargetedUsable(effect) = self.ecs().item.get(effect)?.item_type {
-            match effect {
-                MagicEffect::Lightning => {
-                    const LIGHTNING_RANGE: u32 = 4;
-                    const LIGHTNING_EFFECT: Effect = Effect::Hit {
-                        amount: 12,
-                        damage: Damage::Electricity,
-                    };
-
-                    // TODO: Make an API, more efficient lookup of entities within an area
-
-                    let targets: Vec<Entity> = self
-                        .sphere_volume(origin, LIGHTNING_RANGE)
-                        .0
-                        .into_iter()
-                        .flat_map(|loc| self.entities_at(loc))
-                        .filter(|&e| self.is_mob(e) && Some(e) != caster)
-                        .collect();
-
-                    if let Some(target) = targets.choose(self.rng()) {
-                        msg!(self, "There is a peal of thunder.").send();
-                        let loc = self.location(*target).unwrap();
-                        self.apply_effect(&LIGHTNING_EFFECT, &Volume::point(loc), caster);
-                    } else {
-                        msg!(self, "The spell fizzles.").send();
-                    }
-                }
-                _ => {
-                    msg!(self, "TODO cast untargeted spell {:?}", effect).send();
-                }
+        let item_type = self.item_type(effect).ok_or(ActionError::NotUsable)?;
+        if let ItemType::UntargetedUsable(magic) = item_type {
+            self.cast_untargeted(origin, magic, caster);
+            if caster.is_some() && caster == self.player() {
+                self.identify(effect);
             }
-            caster.map(|e| self.end_turn(e));
-            Some(())
+            caster.map(|e| self.end_turn(e, STANDARD_ACTION));
+            Ok(())
         } else {
-            None
+            Err(ActionError::NotUsable)
         }
     }
 
@@ -299,80 +548,310 @@ pub trait Mutate: Query + Terraform + Sized {
         effect: Entity,
         caster: Option<Entity>,
     ) -> ActionOutcome {
-        if let ItemType::TargetedUsable(effect) = self.ecs().item.get(effect)?.item_type {
-            match effect {
-                MagicEffect::Fireball => {
-                    const FIREBALL_RANGE: u32 = 9;
-                    const FIREBALL_RADIUS: u32 = 2;
-                    const FIREBALL_EFFECT: Effect = Effect::Hit {
-                        amount: 6,
-                        damage: Damage::Fire,
-                    };
-                    let center = self.projected_explosion_center(origin, dir, FIREBALL_RANGE);
-                    let volume = self.sphere_volume(center, FIREBALL_RADIUS);
-                    self.apply_effect(&FIREBALL_EFFECT, &volume, caster);
-                }
-                MagicEffect::Confuse => {
-                    const CONFUSION_RANGE: u32 = 9;
-
-                    let center = self.projected_explosion_center(origin, dir, CONFUSION_RANGE);
-                    self.apply_effect(&Effect::Confuse, &Volume::point(center), caster);
-                }
-                _ => {
-                    msg!(self, "TODO cast directed spell {:?}", effect).send();
-                }
-            }
-            caster.map(|e| self.end_turn(e));
-            Some(())
+        let item_type = self.item_type(effect).ok_or(ActionError::NotUsable)?;
+        if let ItemType::TargetedUsable(magic) = item_type {
+            self.cast_directed(origin, dir, magic, caster);
+            if caster.is_some() && caster == self.player() {
+                self.identify(effect);
+            }
+            caster.map(|e| self.end_turn(e, STANDARD_ACTION));
+            Ok(())
         } else {
-            None
+            Err(ActionError::NotUsable)
+        }
+    }
+
+    /// Cast a learned spell, paying for it with mana.
+    ///
+    /// Targeted spells need a direction, others are cast without one.
+    fn cast_learned(&mut self, e: Entity, spell: Entity, dir: Option<Dir6>) -> ActionOutcome {
+        let origin = self.location(e).ok_or(ActionError::NotInWorld)?;
+        self.check_cast(e, spell)?;
+        let data = self.spell(spell).ok_or(ActionError::NotUsable)?;
+
+        if let Some(mana) = self.ecs_mut().mana.get_mut(e) {
+            mana.points -= data.mana_cost;
+        }
+        let ready_at = self.get_tick() + u64::from(data.cooldown);
+        self.ecs_mut().cooldown.insert(spell, Cooldown { ready_at });
+
+        match dir {
+            Some(dir) => self.cast_directed(origin, dir, data.effect, Some(e)),
+            None => self.cast_untargeted(origin, data.effect, Some(e)),
+        }
+        self.end_turn(e, STANDARD_ACTION);
+        Ok(())
+    }
+
+    /// Produce a magic effect without a specific direction.
+    ///
+    /// Offensive effects that need an aim go for the caster's nearest enemy.
+    fn cast_untargeted(&mut self, origin: Location, effect: MagicEffect, caster: Option<Entity>) {
+        match effect {
+            MagicEffect::Lightning => {
+                const LIGHTNING_RANGE: u32 = 4;
+
+                // TODO: Make an API, more efficient lookup of entities within an area
+
+                let targets: Vec<Entity> = self
+                    .sphere_volume(origin, LIGHTNING_RANGE)
+                    .0
+                    .into_iter()
+                    .flat_map(|loc| self.entities_at(loc))
+                    .filter(|&e| self.is_mob(e) && Some(e) != caster)
+                    .collect();
+
+                if let Some(target) = targets.choose(self.rng()) {
+                    let loc = self.location(*target).unwrap();
+                    self.cast_at(loc, effect, caster);
+                } else {
+                    msg!(self, "The spell fizzles.").send();
+                }
+            }
+            MagicEffect::Heal
+            | MagicEffect::MagicMap
+            | MagicEffect::Identify
+            | MagicEffect::RemoveCurse
+            | MagicEffect::Invisibility
+            | MagicEffect::Regeneration => self.cast_at(origin, effect, caster),
+            MagicEffect::Fireball
+            | MagicEffect::Confuse
+            | MagicEffect::Poison
+            | MagicEffect::Sleep
+            | MagicEffect::Paralyze => {
+                const AIM_RANGE: u32 = 7;
+
+                let target = caster
+                    .and_then(|e| self.nearest_hostile(e, AIM_RANGE))
+                    .and_then(|e| self.location(e));
+                if let Some(loc) = target {
+                    self.cast_at(loc, effect, caster);
+                } else {
+                    msg!(self, "The spell fizzles.").send();
+                }
+            }
+        }
+    }
+
+    /// Produce a magic effect aimed in a direction.
+    ///
+    /// The effect lands on the first creature in the way, or where the path hits a wall.
+    fn cast_directed(
+        &mut self,
+        origin: Location,
+        dir: Dir6,
+        effect: MagicEffect,
+        caster: Option<Entity>,
+    ) {
+        const SPELL_RANGE: u32 = 9;
+
+        if let MagicEffect::MagicMap | MagicEffect::Identify | MagicEffect::RemoveCurse = effect {
+            // Nothing to aim here, the effect goes into the caster's head.
+            self.cast_at(origin, effect, caster);
+        } else {
+            let center = self.projected_explosion_center(origin, dir, SPELL_RANGE);
+            self.cast_at(center, effect, caster);
+        }
+    }
+
+    /// Produce a magic effect centered at a location.
+    fn cast_at(&mut self, center: Location, effect: MagicEffect, caster: Option<Entity>) {
+        match effect {
+            MagicEffect::Heal => {
+                const HEAL_EFFECT: Effect = Effect::Heal(10);
+                self.apply_effect(&HEAL_EFFECT, &Volume::point(center), caster);
+            }
+            MagicEffect::Confuse => {
+                self.apply_effect(&Effect::Confuse, &Volume::point(center), caster);
+            }
+            MagicEffect::Poison => {
+                const POISON_EFFECT: Effect = Effect::Status(Status::Poisoned, 60);
+                self.apply_effect(&POISON_EFFECT, &Volume::point(center), caster);
+            }
+            MagicEffect::Sleep => {
+                const SLEEP_EFFECT: Effect = Effect::Status(Status::Asleep, 120);
+                self.apply_effect(&SLEEP_EFFECT, &Volume::point(center), caster);
+            }
+            MagicEffect::Paralyze => {
+                const PARALYZE_EFFECT: Effect = Effect::Status(Status::Paralyzed, 48);
+                self.apply_effect(&PARALYZE_EFFECT, &Volume::point(center), caster);
+            }
+            MagicEffect::Invisibility => {
+                const INVISIBILITY_EFFECT: Effect = Effect::Status(Status::Invisible, 240);
+                self.apply_effect(&INVISIBILITY_EFFECT, &Volume::point(center), caster);
+            }
+            MagicEffect::Regeneration => {
+                const REGENERATION_EFFECT: Effect = Effect::Status(Status::Regenerating, 120);
+                self.apply_effect(&REGENERATION_EFFECT, &Volume::point(center), caster);
+            }
+            MagicEffect::Lightning => {
+                const LIGHTNING_EFFECT: Effect = Effect::Hit {
+                    amount: 12,
+                    damage: Damage::Electricity,
+                };
+                msg!(self, "There is a peal of thunder.").send();
+                self.apply_effect(&LIGHTNING_EFFECT, &Volume::point(center), caster);
+                self.make_noise(center, EXPLOSION_NOISE);
+            }
+            MagicEffect::Fireball => {
+                const FIREBALL_RADIUS: u32 = 2;
+                const FIREBALL_EFFECT: Effect = Effect::Hit {
+                    amount: 6,
+                    damage: Damage::Fire,
+                };
+                let volume = self.sphere_volume(center, FIREBALL_RADIUS);
+                self.apply_effect(&FIREBALL_EFFECT, &volume, caster);
+                self.make_noise(center, EXPLOSION_NOISE);
+            }
+            MagicEffect::MagicMap => {
+                if let Some(caster) = caster {
+                    self.apply_effect_to_entity(&Effect::MagicMap, caster, Some(caster));
+                }
+            }
+            MagicEffect::Identify => {
+                // Only the player has any use for knowing what things are.
+                if let Some(caster) = caster.filter(|&e| self.is_player(e)) {
+                    let items: Vec<Entity> = Slot::iter()
+                        .filter_map(|&slot| self.entity_equipped(caster, slot))
+                        .filter(|&item| !self.is_identified(item))
+                        .collect();
+                    if items.is_empty() {
+                        msg!(self, "[One] feel[s] as wise as before.").subject(caster).send();
+                    }
+                    for item in items {
+                        self.identify(item);
+                    }
+                }
+            }
+            MagicEffect::RemoveCurse => {
+                if let Some(caster) = caster {
+                    let cursed: Vec<Entity> = Slot::iter()
+                        .filter_map(|&slot| self.entity_equipped(caster, slot))
+                        .filter(|&item| self.is_cursed(item))
+                        .collect();
+                    for &item in &cursed {
+                        self.ecs_mut().item[item].cursed = false;
+                    }
+                    if !cursed.is_empty() && self.is_player(caster) {
+                        msg!(self, "[One] feel[s] as if someone is watching over [one].")
+                            .subject(caster)
+                            .send();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Let the player know the true name of an item's kind.
+    fn identify(&mut self, item: Entity) {
+        if self.is_identified(item) {
+            return;
+        }
+        let true_noun = Noun::new(self.true_name(item)).count(self.quantity(item));
+        msg!(self, "[One] [is] {}.", true_noun.a_name())
+            .subject(item)
+            .send();
+        if let Some(kind) = self.spawn_name(item).map(|n| n.to_string()) {
+            self.identify_kind(kind);
         }
     }
 
     /// The entity spends its action waiting.
     fn idle(&mut self, e: Entity) -> ActionOutcome {
-        if self.consume_nutrition(e) {
-            if let Some(regen) = self.tick_regeneration(e) {
-                self.push_event(Event::Damage {
-                    entity: e,
-                    amount: -regen,
-                });
-            }
+        if let Some(regen) = self.tick_regeneration(e) {
+            self.push_event(Event::Damage {
+                entity: e,
+                amount: -regen,
+            });
         }
-        self.end_turn(e);
-        Some(())
+        self.end_turn(e, STANDARD_ACTION);
+        Ok(())
     }
 
     ////////////////////////////////////////////////////////////////////////////////
 
     fn really_step(&mut self, e: Entity, dir: Dir6) -> ActionOutcome {
-        let origin = self.location(e)?;
+        let origin = self.location(e).ok_or(ActionError::NotInWorld)?;
         let loc = origin.jump(self, dir);
+
+        if let Some(other) = self.mob_at(loc) {
+            if self.can_displace(e, other) {
+                self.place_entity(e, loc);
+                self.place_entity(other, origin);
+                msg!(self, "[One] swap[s] places with [another].")
+                    .subject(e)
+                    .object(other)
+                    .send();
+                self.trigger_instants(e);
+                self.trigger_instants(other);
+                self.end_turn(e, STANDARD_ACTION);
+                return Ok(());
+            }
+        }
+
         if self.can_enter(e, loc) {
+            let opens_door = self.terrain(loc).is_door();
             self.place_entity(e, loc);
+            if opens_door {
+                self.make_noise(loc, DOOR_NOISE);
+            }
+            self.trigger_instants(e);
 
-            let delay = self.action_delay(e);
+            let delay = self.action_delay(e, STANDARD_ACTION);
             debug_assert!(delay > 0);
             if let Some(anim) = self.ecs_mut().anim.get_mut(e) {
                 anim.tween_from = origin;
                 anim.tween_current = delay - 1;
                 anim.tween_max = delay;
             }
-            self.end_turn(e);
-            return Some(());
+            self.end_turn(e, STANDARD_ACTION);
+            return Ok(());
         }
 
-        None
+        Err(ActionError::Blocked)
+    }
+
+    /// Use up the instant items where a mob has stepped.
+    fn trigger_instants(&mut self, e: Entity) {
+        let loc = match self.location(e) {
+            Some(loc) => loc,
+            None => return,
+        };
+
+        for item in self.entities_at(loc) {
+            match self.item_type(item) {
+                Some(ItemType::Instant(effect)) => {
+                    if self.player_sees(loc) {
+                        msg!(self, "[One] absorb[s] [another].")
+                            .subject(e)
+                            .object(item)
+                            .send();
+                    }
+                    self.kill_entity(item);
+                    self.cast_untargeted(loc, effect, Some(e));
+                }
+                Some(ItemType::Gold) if self.is_player(e) => {
+                    let amount = self.uses_left(item);
+                    msg!(self, "[One] pick[s] up {} gold.", amount)
+                        .subject(e)
+                        .send();
+                    self.kill_entity(item);
+                    self.add_gold(amount);
+                }
+                _ => {}
+            }
+        }
     }
 
     fn really_melee(&mut self, e: Entity, dir: Dir6) -> ActionOutcome {
-        let loc = self.location(e)?;
-        let target = self.mob_at(loc.jump(self, dir))?;
+        let loc = self.location(e).ok_or(ActionError::NotInWorld)?;
+        let target_loc = loc.jump(self, dir);
+        let target = self.mob_at(target_loc).ok_or(ActionError::NoTarget)?;
 
         // XXX: Using power stat for damage, should this be different?
         // Do +5 since dmg 1 is really, really useless.
-        let advantage =
-            self.stats(e).attack - self.stats(target).defense + 2 * self.stats(target).armor;
+        // Armor is left out, it gets its due when the damage is absorbed by the armor pool.
+        let advantage = self.stats(e).attack - self.stats(target).defense;
         let damage = attack_damage(roll(self.rng()), advantage, 5 + self.stats(e).power);
 
         if damage == 0 {
@@ -387,8 +866,57 @@ pub trait Mutate: Query + Terraform + Sized {
                 .send();
         }
         self.damage(target, damage, Damage::Physical, Some(e));
-        self.end_turn(e);
-        Some(())
+        if damage > 0 && self.is_alive(target) {
+            if self.has_intrinsic(e, Intrinsic::Venomous) {
+                self.gain_status(target, Status::Poisoned, 60);
+            }
+            if self.has_intrinsic(e, Intrinsic::Fiery) {
+                self.gain_status(target, Status::Burning, 36);
+            }
+            for on_hit in self.on_hit_effects(e) {
+                if self.is_alive(target) && self.rng().with_chance(on_hit.chance) {
+                    self.apply_effect_to_entity(&on_hit.effect, target, Some(e));
+                }
+            }
+        }
+        self.make_noise(target_loc, COMBAT_NOISE);
+        self.end_turn(e, MELEE_ACTION);
+        Ok(())
+    }
+
+    fn really_shoot(&mut self, e: Entity, dir: Dir6) -> ActionOutcome {
+        self.location(e).ok_or(ActionError::NotInWorld)?;
+        let range = self.stats(e).ranged_range;
+        if range == 0 {
+            return Err(ActionError::NoRangedWeapon);
+        }
+
+        // `find_target` stops at terrain that blocks shots, the shot is wasted if it doesn't hit
+        // anything.
+        if let Some(target) = self.find_target(e, dir, range as usize) {
+            let advantage = self.stats(e).attack - self.stats(target).defense;
+            let damage = attack_damage(roll(self.rng()), advantage, self.stats(e).ranged_power);
+
+            if damage == 0 {
+                msg!(self, "[One] shoot[s] at [another] and miss[es].")
+                    .subject(e)
+                    .object(target)
+                    .send();
+            } else {
+                msg!(self, "[One] shoot[s] [another] for {}.", damage)
+                    .subject(e)
+                    .object(target)
+                    .send();
+            }
+            self.damage(target, damage, Damage::Physical, Some(e));
+            if let Some(target_loc) = self.location(target) {
+                self.make_noise(target_loc, COMBAT_NOISE);
+            }
+        } else {
+            msg!(self, "[One] shoot[s] at nothing.").subject(e).send();
+        }
+        self.end_turn(e, STANDARD_ACTION);
+        Ok(())
     }
 
     /// Randomly make a confused mob move erratically.
@@ -426,11 +954,58 @@ pub trait Mutate: Query + Terraform + Sized {
             self.notify_attacked_by(e, attacker);
         }
 
+        let mut amount = amount;
+        if amount > 0 && self.resists(e, damage_type) {
+            amount /= 2;
+            if self.player_sees_entity(e) {
+                msg!(
+                    self,
+                    "[One] resist[s] the {}.",
+                    match damage_type {
+                        Damage::Fire => "fire",
+                        Damage::Electricity => "electricity",
+                        _ => "cold",
+                    }
+                )
+                .subject(e)
+                .send();
+            }
+        } else if amount > 0 && self.is_vulnerable_to(e, damage_type) {
+            amount *= 2;
+            if self.player_sees_entity(e) {
+                msg!(
+                    self,
+                    "[One] [is] badly {}.",
+                    match damage_type {
+                        Damage::Fire => "burned",
+                        Damage::Electricity => "shocked",
+                        _ => "frozen",
+                    }
+                )
+                .subject(e)
+                .send();
+            }
+        }
+
+        // Getting hit snaps you out of magical sleep.
+        if amount > 0 && self.has_status(e, Status::Asleep) {
+            self.lose_status(e, Status::Asleep);
+        }
+
         let max_hp = self.max_hp(e);
 
         let mut kill = false;
+        let mut wounds = 0;
         if let Some(health) = self.ecs_mut().health.get_mut(e) {
             if amount > 0 {
+                // Armor soaks up physical hits until it's worn away.
+                if damage_type == Damage::Physical {
+                    let absorbed = amount.min(health.armor);
+                    health.armor -= absorbed;
+                    amount -= absorbed;
+                }
+
+                wounds = amount;
                 health.wounds += amount;
 
                 if health.wounds > max_hp {
@@ -439,6 +1014,13 @@ pub trait Mutate: Query + Terraform + Sized {
             }
         }
 
+        if wounds > 0 {
+            self.push_event(Event::Damage {
+                entity: e,
+                amount: wounds,
+            });
+        }
+
         if kill {
             if let Some(loc) = self.location(e) {
                 if self.player_sees(loc) {
@@ -451,24 +1033,122 @@ pub trait Mutate: Query + Terraform + Sized {
                             Damage::Fire => "burn[s] to ash",
                             Damage::Electricity => "[is] electrocuted",
                             Damage::Cold => "shatter[s] to frozen pieces",
+                            Damage::Poison => "succumb[s] to poison",
+                            Damage::Starvation => "starve[s] to death",
                         }
                     )
                     .subject(e)
                     .send();
                 }
             }
-            self.kill_entity(e);
+            let killer = source.filter(|&killer| killer != e);
+            if let Some(killer) = killer {
+                let xp = self.xp_value(e);
+                self.gain_experience(killer, xp);
+            }
+            self.die(e, killer);
+        }
+    }
+
+    /// Award experience to an entity, raising its level for every threshold crossed.
+    fn gain_experience(&mut self, e: Entity, amount: u32) {
+        let mut new_levels = Vec::new();
+        if let Some(experience) = self.ecs_mut().experience.get_mut(e) {
+            experience.xp += amount;
+            while experience.xp >= experience.next_level_xp() {
+                experience.level += 1;
+                new_levels.push(experience.level);
+            }
+        }
+
+        for level in new_levels {
+            self.level_up(e, level);
+        }
+    }
+
+    /// Grow the entity's base stats for reaching a new level.
+    fn level_up(&mut self, e: Entity, level: u32) {
+        const POWER_PER_LEVEL: i32 = 2;
+        const MANA_PER_LEVEL: i32 = 1;
+
+        if let Some(stats) = self.ecs_mut().stats.get_mut(e) {
+            stats.base = stats.base + Stats::new(POWER_PER_LEVEL, &[]).mana(MANA_PER_LEVEL);
+        }
+        self.rebuild_stats(e);
+
+        msg!(self, "[One] [is] now level {}.", level).subject(e).send();
+        self.push_event(Event::LevelUp { entity: e, level });
+    }
+
+    /// Run the on-death effects of an entity and mark it as dead.
+    ///
+    /// The entity drops everything it carries, may leave a corpse behind and may explode. Anything
+    /// caught in the explosion is on the killer's account.
+    fn die(&mut self, e: Entity, killer: Option<Entity>) {
+        let loc = match self.location(e) {
+            Some(loc) => loc,
+            None => return,
+        };
+
+        if self.has_intrinsic(e, Intrinsic::LeavesCorpse) {
+            let loadout = Loadout::new()
+                .c(Desc::new(&format!("{} corpse", self.entity_name(e)), Icon::Corpse))
+                .c(Item {
+                    item_type: ItemType::Corpse,
+                    charges: 1,
+                    quantity: 1,
+                    cursed: false,
+                    requirements: Default::default(),
+                    enchantment: 0,
+                    affix: None,
+                    on_hit: Vec::new(),
+                    auras: Vec::new(),
+                });
+            self.spawn(&loadout, loc);
+        }
+
+        for &slot in Slot::iter() {
+            if let Some(item) = self.entity_equipped(e, slot) {
+                self.place_entity(item, loc);
+            }
+        }
+
+        // Remove the entity before the explosion so it doesn't get caught in its own blast.
+        self.kill_entity(e);
+
+        if self.has_intrinsic(e, Intrinsic::Deathsplosion) {
+            const DEATHSPLOSION_RADIUS: u32 = 1;
+            let effect = Effect::Hit {
+                amount: (self.max_hp(e) / 2).max(1) as u32,
+                damage: Damage::Fire,
+            };
+
+            if self.player_sees(loc) {
+                msg!(self, "[One] explode[s]!").subject(e).send();
+            }
+            let volume = self.sphere_volume(loc, DEATHSPLOSION_RADIUS);
+            self.apply_effect(&effect, &volume, killer);
+            self.make_noise(loc, EXPLOSION_NOISE);
         }
     }
 
     /// Do a single step of natural regeneration for a creature.
     ///
-    /// Return amount of health gained, or None if at full health.
+    /// Return amount of health gained, or None if at full health or starving.
     fn tick_regeneration(&mut self, e: Entity) -> Option<i32> {
+        // Starving bodies don't heal.
+        if self.hunger(e).map_or(false, |h| h >= Hunger::Weak) {
+            return None;
+        }
         let max_hp = self.max_hp(e);
+        let max_armor = self.stats(e).armor;
         let increase = (max_hp / 30).max(1);
 
         let health = self.ecs_mut().health.get_mut(e)?;
+        // Worn armor gets patched up alongside wounds.
+        if health.armor < max_armor {
+            health.armor = (health.armor + increase).min(max_armor);
+        }
         if health.wounds > 0 {
             let increase = increase.min(health.wounds);
             health.wounds -= increase;
@@ -487,6 +1167,7 @@ pub trait Mutate: Query + Terraform + Sized {
             // Annihilate any existing entities in the drop zone.
             let es = self.entities_at(loc);
             for &e in &es {
+                self.kill_entity(e);
                 self.remove_entity(e);
             }
 
@@ -527,27 +1208,58 @@ pub trait Mutate: Query + Terraform + Sized {
             let loadout = spec::PLAYER_SPAWN
                 .sample(self.rng())
                 .c(Brain::player())
-                .c(MapMemory::default());
+                .c(MapMemory::default())
+                .c(Nutrition::default())
+                .c(Experience::default())
+                .c(Mana::default());
             let player = self.spawn(&loadout, loc);
+            // Start out with a full mana pool.
+            self.ecs_mut().mana[player].points = self.max_mana(player);
             self.set_player(Some(player));
         }
     }
 
     fn apply_effect_to_entity(&mut self, effect: &Effect, target: Entity, source: Option<Entity>) {
-        use crate::effect::Effect::*;
         match *effect {
-            Heal(_amount) => {
-                unimplemented!();
+            Effect::Heal(amount) => {
+                let healed = match self.ecs_mut().health.get_mut(target) {
+                    Some(health) => {
+                        let healed = (amount as i32).min(health.wounds);
+                        health.wounds -= healed;
+                        healed
+                    }
+                    None => 0,
+                };
+                if healed > 0 {
+                    self.push_event(Event::Damage {
+                        entity: target,
+                        amount: -healed,
+                    });
+                }
+                if self.player_sees_entity(target) {
+                    msg!(self, "[One] [is] healed.").subject(target).send();
+                }
             }
-            Hit { amount, damage } => {
+            Effect::Hit { amount, damage } => {
                 self.damage(target, amount as i32, damage, source);
             }
-            Confuse => {
+            Effect::Confuse => {
                 self.gain_status(target, Status::Confused, 40);
-                msg!(self, "[One] [is] confused.").subject(target).send();
             }
-            MagicMap => {
-                unimplemented!();
+            Effect::Status(status, duration) => {
+                self.gain_status(target, status, duration);
+            }
+            Effect::MagicMap => {
+                let sector = match self.location(target) {
+                    Some(loc) => loc.sector(),
+                    None => return,
+                };
+                if let Some(memory) = self.ecs_mut().map_memory.get_mut(target) {
+                    memory.remembered.extend(sector.iter());
+                }
+                if self.is_player(target) {
+                    msg!(self, "A map of the surroundings forms in your mind.").send();
+                }
             }
         }
     }
@@ -565,9 +1277,13 @@ pub trait Mutate: Query + Terraform + Sized {
     }
 
     fn drain_charge(&mut self, item: Entity) {
+        let consumable = self.destroy_after_use(item);
         let mut emptied = false;
         if let Some(i) = self.ecs_mut().item.get_mut(item) {
-            if i.charges > 0 {
+            if consumable && i.charges == 1 && i.quantity > 1 {
+                // Use up one item from the stack, the rest are untouched.
+                i.quantity -= 1;
+            } else if i.charges > 0 {
                 i.charges -= 1;
 
                 if i.charges == 0 {
@@ -576,24 +1292,93 @@ pub trait Mutate: Query + Terraform + Sized {
             }
         }
 
-        if emptied {
-            if self.destroy_after_use(item) {
-                self.kill_entity(item);
-            }
+        if emptied && consumable {
+            self.destroy_item(item);
+        }
+    }
+
+    /// Move all items in `item` to the `stack` and destroy the now empty item.
+    fn merge_stack(&mut self, stack: Entity, item: Entity) {
+        let quantity = self.quantity(item);
+        if let Some(i) = self.ecs_mut().item.get_mut(stack) {
+            i.quantity += quantity;
+        }
+        self.kill_entity(item);
+    }
+
+    /// Split `count` items off a stack into a new entity.
+    ///
+    /// If the split takes the whole stack, the item itself is returned. The new entity is not
+    /// placed anywhere.
+    fn split_stack(&mut self, item: Entity, count: u32) -> Entity {
+        let quantity = self.quantity(item);
+        if count >= quantity {
+            return item;
         }
+
+        let split = Loadout::get(self.ecs(), item).make(self.ecs_mut());
+        self.ecs_mut().item[item].quantity = quantity - count;
+        self.ecs_mut().item[split].quantity = count;
+        split
     }
 
     /// Run autonomous updates on entity that happen each turn
     ///
     /// This runs regardless of the action speed or awakeness status of the entity. The exact same
     /// is run for player and AI entities.
-    fn heartbeat(&mut self, e: Entity) { self.tick_statuses(e); }
+    fn heartbeat(&mut self, e: Entity) {
+        self.tick_statuses(e);
+        self.tick_auras(e);
+        self.tick_mana(e);
+    }
+
+    /// Slowly refill the entity's mana pool.
+    fn tick_mana(&mut self, e: Entity) {
+        const MANA_INTERVAL: u64 = 24;
+
+        if self.get_tick() % MANA_INTERVAL != 0 {
+            return;
+        }
+        let max_mana = self.max_mana(e);
+        if let Some(mana) = self.ecs_mut().mana.get_mut(e) {
+            if mana.points < max_mana {
+                mana.points += 1;
+            }
+        }
+    }
+
+    /// Apply the passive effects of the entity's equipment.
+    fn tick_auras(&mut self, e: Entity) {
+        // Regeneration from gear is slower than from the status.
+        const REGENERATION_INTERVAL: u64 = 24;
+
+        for aura in self.auras(e) {
+            match aura {
+                Aura::Regeneration => {
+                    if self.get_tick() % REGENERATION_INTERVAL != 0 {
+                        continue;
+                    }
+                    if let Some(regen) = self.tick_regeneration(e) {
+                        self.push_event(Event::Damage {
+                            entity: e,
+                            amount: -regen,
+                        });
+                    }
+                }
+                // Keep the status topped up, it wears off on its own once the gear comes off.
+                Aura::SeeInvisible => self.gain_status(e, Status::SeeInvisible, 2),
+                // Light is accounted for when computing field of view.
+                Aura::Light(_) => {}
+            }
+        }
+    }
 
     fn gain_status(&mut self, e: Entity, status: Status, duration: u32) {
         if duration == 0 {
             return;
         }
 
+        let mut is_new = false;
         if let Some(statuses) = self.ecs_mut().status.get_mut(e) {
             if let Some(current_duration) = statuses.get(&status).cloned() {
                 if duration > current_duration {
@@ -601,27 +1386,121 @@ pub trait Mutate: Query + Terraform + Sized {
                     statuses.insert(status, duration);
                 }
             } else {
-                // TODO: Special stuff when status first goes into effect goes here
                 statuses.insert(status, duration);
+                is_new = true;
             }
         }
+
+        if is_new {
+            self.status_gained(e, status);
+        }
+    }
+
+    /// Remove a status before it runs out.
+    fn lose_status(&mut self, e: Entity, status: Status) {
+        let removed = self
+            .ecs_mut()
+            .status
+            .get_mut(e)
+            .and_then(|statuses| statuses.remove(&status))
+            .is_some();
+
+        if removed {
+            self.status_expired(e, status);
+        }
     }
 
     fn tick_statuses(&mut self, e: Entity) {
-        if let Some(statuses) = self.ecs_mut().status.get_mut(e) {
-            let mut remove = Vec::new();
+        let mut ongoing = Vec::new();
+        let mut remove = Vec::new();
 
+        if let Some(statuses) = self.ecs_mut().status.get_mut(e) {
             for (k, d) in statuses.iter_mut() {
                 *d -= 1;
                 if *d == 0 {
                     remove.push(*k);
+                } else {
+                    ongoing.push((*k, *d));
                 }
             }
 
-            // TODO: Special stuff when status goes out of effect for dropped statuses.
-            for k in remove.into_iter() {
-                statuses.remove(&k);
+            for k in &remove {
+                statuses.remove(k);
+            }
+        }
+
+        for (status, frames_left) in ongoing {
+            if !self.is_alive(e) {
+                return;
+            }
+            self.status_tick(e, status, frames_left);
+        }
+
+        for status in remove {
+            self.status_expired(e, status);
+        }
+    }
+
+    /// Hook for when an entity first gets a status.
+    fn status_gained(&mut self, e: Entity, status: Status) {
+        let text = match status {
+            Status::Confused => "[One] [is] confused.",
+            Status::Slowed => "[One] slow[s] down.",
+            Status::Hasted => "[One] speed[s] up.",
+            Status::Poisoned => "[One] [is] poisoned.",
+            Status::Regenerating => "[One] start[s] to regenerate.",
+            Status::Asleep => "[One] fall[s] asleep.",
+            Status::Paralyzed => "[One] [is] paralyzed.",
+            Status::Invisible => "[One] vanish[es] from sight.",
+            Status::Burning => "[One] catch[es] fire.",
+            Status::SeeInvisible => "[One's] eyes tingle.",
+            Status::Dead => return,
+        };
+
+        if self.player_sees_entity(e) {
+            msg!(self, text).subject(e).send();
+        }
+    }
+
+    /// Hook for every frame an entity has an ongoing status.
+    fn status_tick(&mut self, e: Entity, status: Status, frames_left: u32) {
+        // Effects over time kick in once every normal-speed turn.
+        const STATUS_INTERVAL: u32 = 12;
+        let on_interval = frames_left % STATUS_INTERVAL == 0;
+
+        match status {
+            Status::Poisoned if on_interval => self.damage(e, 1, Damage::Poison, None),
+            Status::Burning if on_interval => self.damage(e, 2, Damage::Fire, None),
+            Status::Regenerating if on_interval => {
+                if let Some(regen) = self.tick_regeneration(e) {
+                    self.push_event(Event::Damage {
+                        entity: e,
+                        amount: -regen,
+                    });
+                }
             }
+            _ => {}
+        }
+    }
+
+    /// Hook for when a status runs out or is removed.
+    fn status_expired(&mut self, e: Entity, status: Status) {
+        let text = match status {
+            Status::Confused => "[One] [is] no longer confused.",
+            Status::Slowed => "[One] [is] no longer slowed.",
+            Status::Hasted => "[One] slow[s] back down.",
+            Status::Poisoned => "[One] [is] no longer poisoned.",
+            Status::Regenerating => "[One] stop[s] regenerating.",
+            Status::Asleep => "[One] wake[s] up.",
+            Status::Paralyzed => "[One] can move again.",
+            Status::Invisible => "[One] reappear[s].",
+            Status::Burning => "[One] stop[s] burning.",
+            Status::SeeInvisible => "[One's] eyes stop tingling.",
+            Status::Dead => return,
+        };
+
+        if self.is_alive(e) && self.player_sees_entity(e) {
+            msg!(self, text).subject(e).send();
         }
     }
 
@@ -644,15 +1523,527 @@ pub trait Mutate: Query + Terraform + Sized {
             }
         }
 
+        // Armor pool grows and shrinks along with the armor stat.
+        let armor_change = stats.armor - self.stats(e).armor;
+        if let Some(health) = self.ecs_mut().health.get_mut(e) {
+            health.armor = (health.armor + armor_change).min(stats.armor).max(0);
+        }
+
         // Set the derived stats.
         self.ecs_mut().stats[e].actual = stats;
     }
 
-    /// Consume one unit of nutrition
+    /// Consume one unit of nutrition.
     ///
-    /// Return false if the entity has an empty stomach.
-    fn consume_nutrition(&mut self, _: Entity) -> bool {
-        // TODO nutrition system
-        true
+    /// Creatures close to an empty stomach faint now and then, and ones with an empty stomach
+    /// starve.
+    fn consume_nutrition(&mut self, e: Entity) {
+        // Fainting spells are rare enough to let you look for food.
+        const FAINT_CHANCE: u32 = 20;
+
+        let old_hunger = match self.hunger(e) {
+            Some(hunger) => hunger,
+            None => return,
+        };
+        let satiation = {
+            let nutrition = &mut self.ecs_mut().nutrition[e];
+            nutrition.satiation = nutrition.satiation.saturating_sub(1);
+            nutrition.satiation
+        };
+        let hunger = self.ecs().nutrition[e].hunger();
+
+        if hunger > old_hunger && self.is_player(e) {
+            match hunger {
+                Hunger::Hungry => msg!(self, "[One] [is] getting hungry.").subject(e).send(),
+                Hunger::Weak => msg!(self, "[One] feel[s] weak from hunger.").subject(e).send(),
+                Hunger::Fainting => msg!(self, "[One] [is] fainting from hunger.")
+                    .subject(e)
+                    .send(),
+                Hunger::Satiated => {}
+            }
+        }
+
+        if satiation == 0 {
+            self.damage(e, 1, Damage::Starvation, None);
+        } else if hunger == Hunger::Fainting
+            && !self.has_status(e, Status::Paralyzed)
+            && self.rng().one_chance_in(FAINT_CHANCE)
+        {
+            msg!(self, "[One] faint[s].").subject(e).send();
+            self.gain_status(e, Status::Paralyzed, 36);
+        }
+    }
+
+    /// Eat a food item or a corpse.
+    fn eat(&mut self, e: Entity, item: Entity) -> ActionOutcome {
+        let amount = self.nutrition_value(item).ok_or(ActionError::NotUsable)?;
+        // Only eat one item off a stack.
+        let item = self.split_stack(item, 1);
+
+        if self.is_player(e) {
+            msg!(self, "[One] eat[s] [another].")
+                .subject(e)
+                .object(item)
+                .send();
+        }
+        self.destroy_item(item);
+        if let Some(nutrition) = self.ecs_mut().nutrition.get_mut(e) {
+            nutrition.satiation = (nutrition.satiation + amount).min(Nutrition::MAX);
+        }
+
+        self.end_turn(e, STANDARD_ACTION);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Mutate;
+    use crate::command::{ActionError, Command};
+    use crate::components::{Hunger, Status};
+    use crate::effect::Damage;
+    use crate::event::Event;
+    use crate::item::{ItemType, MagicEffect, Slot};
+    use crate::location::Location;
+    use crate::query::Query;
+    use crate::test_util::{
+        give_item, peaceful_world, saw_message, set_item_type, spawn, spawn_next_to_player,
+        wait_for_player,
+    };
+    use calx::Incremental;
+    use calx_ecs::Entity;
+
+    #[test]
+    fn test_cast_heal() {
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+        world.ecs_mut().health[player].wounds = 5;
+
+        let slot = give_item(&mut world, "potion of healing");
+        world.update(&Command::UseItem(slot));
+        assert_eq!(world.ecs().health[player].wounds, 0);
+        assert!(world.events().iter().any(|e| match e {
+            Event::Damage { entity, amount } => *entity == player && *amount == -5,
+            _ => false,
+        }));
+
+        // Directed healing works on others.
+        wait_for_player(&mut world);
+        let (hireling, dir) = spawn_next_to_player(&mut world, "hireling");
+        world.ecs_mut().health[hireling].wounds = 3;
+        let slot = give_item(&mut world, "wand of confusion");
+        set_item_type(&mut world, slot, ItemType::TargetedUsable(MagicEffect::Heal));
+        world.update(&Command::Zap(slot, dir));
+        assert_eq!(world.ecs().health[hireling].wounds, 0);
+    }
+
+    #[test]
+    fn test_cast_magic_map() {
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+        let sector = world.location(player).unwrap().sector();
+        assert!(sector.iter().any(|loc| world.fov_status(loc).is_none()));
+
+        let slot = give_item(&mut world, "scroll of magic mapping");
+        world.update(&Command::UseItem(slot));
+        assert!(sector.iter().all(|loc| world.fov_status(loc).is_some()));
+    }
+
+    #[test]
+    fn test_cast_attack_spells() {
+        let mut world = peaceful_world(1);
+        // Make the player sturdy enough to get caught in their own fireball.
+        let player = world.player().unwrap();
+        world.ecs_mut().stats[player].base.power = 100;
+        world.rebuild_stats(player);
+
+        // Untargeted spells that need aiming go for the nearest enemy...
+        let (dreg, _) = spawn_next_to_player(&mut world, "dreg");
+        let slot = give_item(&mut world, "scroll of lightning");
+        set_item_type(&mut world, slot, ItemType::UntargetedUsable(MagicEffect::Confuse));
+        world.update(&Command::UseItem(slot));
+        assert!(world.has_status(dreg, Status::Confused));
+
+        wait_for_player(&mut world);
+        let slot = give_item(&mut world, "scroll of lightning");
+        set_item_type(&mut world, slot, ItemType::UntargetedUsable(MagicEffect::Fireball));
+        world.update(&Command::UseItem(slot));
+        assert!(!world.is_alive(dreg));
+
+        // ...and fizzle when there are none.
+        wait_for_player(&mut world);
+        let slot = give_item(&mut world, "scroll of lightning");
+        set_item_type(&mut world, slot, ItemType::UntargetedUsable(MagicEffect::Fireball));
+        world.update(&Command::UseItem(slot));
+        assert!(saw_message(&world, "The spell fizzles."));
+
+        // Directed lightning hits the creature in the line of fire.
+        wait_for_player(&mut world);
+        let (dreg, dir) = spawn_next_to_player(&mut world, "dreg");
+        let slot = give_item(&mut world, "wand of fireball");
+        set_item_type(&mut world, slot, ItemType::TargetedUsable(MagicEffect::Lightning));
+        world.update(&Command::Zap(slot, dir));
+        assert!(saw_message(&world, "There is a peal of thunder."));
+        assert!(!world.is_alive(dreg));
+    }
+
+    #[test]
+    fn test_item_stacks() {
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+        let loc = world.location(player).unwrap();
+        world.identify_kind("potion of healing".to_string());
+
+        // Matching items get merged into one slot when picked up.
+        let slot = give_item(&mut world, "potion of healing");
+        let stack = world.entity_equipped(player, slot).unwrap();
+        for _ in 0..2 {
+            let potion = spawn(&mut world, "potion of healing", loc);
+            world.entity_take(player, potion).unwrap();
+        }
+        assert_eq!(world.quantity(stack), 3);
+        assert_eq!(world.free_bag_slot(player), Some(Slot::InventoryK));
+        assert_eq!(world.noun(stack).a_name(), "3 potions of healing");
+
+        let pile = spawn(&mut world, "potion of healing", loc);
+        world.ecs_mut().item[pile].quantity = 2;
+        world.entity_take(player, pile).unwrap();
+        assert!(saw_message(&world, "You pick up the 2 potions of healing."));
+        assert_eq!(world.quantity(stack), 5);
+
+        // Different items don't stack.
+        let scroll = spawn(&mut world, "scroll of lightning", loc);
+        world.entity_take(player, scroll).unwrap();
+        assert_eq!(world.entity_equipped(player, Slot::InventoryK), Some(scroll));
+
+        // Neither do cursed ones.
+        let cursed = spawn(&mut world, "potion of healing", loc);
+        world.ecs_mut().item[cursed].cursed = true;
+        assert!(!world.can_stack(stack, cursed));
+        world.ecs_mut().item[cursed].cursed = false;
+        assert!(world.can_stack(stack, cursed));
+        world.ecs_mut().item[cursed].enchantment = 1;
+        assert!(!world.can_stack(stack, cursed));
+
+        // Using an item takes one from the stack.
+        wait_for_player(&mut world);
+        world.update(&Command::UseItem(slot));
+        assert_eq!(world.entity_equipped(player, slot), Some(stack));
+        assert_eq!(world.quantity(stack), 4);
+
+        // Dropping can split the stack.
+        wait_for_player(&mut world);
+        let old_entities: Vec<Entity> = world.entities().cloned().collect();
+        world.update(&Command::Drop(slot, 3));
+        assert_eq!(world.quantity(stack), 1);
+        let dropped = world
+            .entities()
+            .cloned()
+            .find(|e| !old_entities.contains(e))
+            .unwrap();
+        assert_eq!(world.spawn_name(dropped), Some("potion of healing"));
+        assert_eq!(world.quantity(dropped), 3);
+        assert!(world.location(dropped).is_some());
+        assert_eq!(
+            world.check_command(&Command::Drop(slot, 0)),
+            Err(ActionError::NothingToDrop)
+        );
+
+        // The last item in the stack is used up normally.
+        wait_for_player(&mut world);
+        world.update(&Command::UseItem(slot));
+        assert_eq!(world.entity_equipped(player, slot), None);
+    }
+
+    #[test]
+    fn test_equip_restrictions() {
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+
+        // Cursed items won't come off once equipped.
+        let slot = give_item(&mut world, "helmet");
+        let helmet = world.entity_equipped(player, slot).unwrap();
+        world.ecs_mut().item[helmet].cursed = true;
+        world.update(&Command::Equip(slot));
+        assert_eq!(world.entity_equipped(player, Slot::Head), Some(helmet));
+        let message = format!("The {} binds itself to you!", world.entity_name(helmet));
+        assert!(saw_message(&world, &message));
+        assert_eq!(world.check_command(&Command::Equip(Slot::Head)), Err(ActionError::Cursed));
+        assert_eq!(world.check_command(&Command::Drop(Slot::Head, 1)), Err(ActionError::Cursed));
+
+        wait_for_player(&mut world);
+        world.identify_kind("scroll of remove curse".to_string());
+        let slot = give_item(&mut world, "scroll of remove curse");
+        world.update(&Command::UseItem(slot));
+        assert!(saw_message(&world, "You feel as if someone is watching over you."));
+        assert!(!world.is_cursed(helmet));
+        assert_eq!(world.check_command(&Command::Equip(Slot::Head)), Ok(()));
+
+        // Equipment can demand strength and hands.
+        wait_for_player(&mut world);
+        let slot = give_item(&mut world, "crossbow");
+        let crossbow = world.entity_equipped(player, slot).unwrap();
+        assert_eq!(world.check_command(&Command::Equip(slot)), Err(ActionError::TooWeak));
+        world.ecs_mut().stats[player].base.power = 12;
+        world.rebuild_stats(player);
+        assert_eq!(world.check_command(&Command::Equip(slot)), Ok(()));
+
+        let snake = spawn(&mut world, "snake", Location::new(0, 0, 0));
+        world.ecs_mut().stats[snake].base.power = 100;
+        world.rebuild_stats(snake);
+        assert_eq!(world.free_equip_slot(snake, crossbow), Err(ActionError::NoHands));
+    }
+
+    #[test]
+    fn test_equipment_effects() {
+        let mut world = peaceful_world(1);
+        // Keep the player and their sparring partner alive through the fight.
+        let player = world.player().unwrap();
+        world.ecs_mut().stats[player].base.power = 100;
+        world.rebuild_stats(player);
+
+        // Weapons apply their on-hit effects to what they strike.
+        let slot = give_item(&mut world, "mace");
+        let mace = world.entity_equipped(player, slot).unwrap();
+        world.ecs_mut().item[mace].cursed = false;
+        world.ecs_mut().item[mace].on_hit[0].chance = 1.0;
+        world.update(&Command::Equip(slot));
+        assert_eq!(world.entity_equipped(player, Slot::Melee), Some(mace));
+
+        let (dreg, dir) = spawn_next_to_player(&mut world, "dreg");
+        world.ecs_mut().stats[dreg].base.power = 100;
+        world.rebuild_stats(dreg);
+        for _ in 0..20 {
+            wait_for_player(&mut world);
+            if world.has_status(dreg, Status::Confused) {
+                break;
+            }
+            world.update(&Command::Melee(dir));
+        }
+        assert!(world.has_status(dreg, Status::Confused));
+
+        // Invisible creatures show up with an amulet of true sight.
+        world.gain_status(dreg, Status::Invisible, 1000);
+        assert_eq!(world.nearest_hostile(player, 10), None);
+        wait_for_player(&mut world);
+        let slot = give_item(&mut world, "amulet of true sight");
+        world.update(&Command::Equip(slot));
+        wait_for_player(&mut world);
+        assert!(world.has_status(player, Status::SeeInvisible));
+        assert_eq!(world.nearest_hostile(player, 10), Some(dreg));
+
+        // Regeneration aura heals over time.
+        let slot = give_item(&mut world, "ring of regeneration");
+        world.update(&Command::Equip(slot));
+        while world.get_tick() % 24 != 0 {
+            let cmd = if world.player_can_act() { Command::Pass } else { Command::Wait };
+            world.update(&cmd);
+        }
+        world.ecs_mut().health[player].wounds = 5;
+        world.heartbeat(player);
+        assert!(world.ecs().health[player].wounds < 5);
+
+        // Light sources extend sight underground.
+        wait_for_player(&mut world);
+        assert_eq!(world.light_radius(player), 0);
+        let slot = give_item(&mut world, "lantern");
+        world.update(&Command::Equip(slot));
+        assert_eq!(world.light_radius(player), 3);
+    }
+
+    #[test]
+    fn test_containers() {
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+        let loc = world.location(player).unwrap();
+
+        // Worldgen fills containers with loot.
+        assert!(world
+            .entities()
+            .filter(|&&e| world.is_container(e))
+            .all(|&e| !world.contents(e).is_empty()));
+
+        let bag_slot = give_item(&mut world, "bag");
+        let bag = world.entity_equipped(player, bag_slot).unwrap();
+        world.identify_kind("potion of healing".to_string());
+        for _ in 0..2 {
+            wait_for_player(&mut world);
+            let slot = give_item(&mut world, "potion of healing");
+            world.update(&Command::PutIn(slot, bag_slot));
+            assert_eq!(world.entity_equipped(player, slot), None);
+        }
+        assert!(saw_message(&world, "You put the potion of healing in the bag."));
+        // Stacks merge inside containers too.
+        let contents = world.contents(bag);
+        assert_eq!(contents.len(), 1);
+        assert_eq!(world.quantity(contents[0]), 2);
+        assert_eq!(world.location(contents[0]), Some(loc));
+
+        // Containers don't go inside other containers.
+        let box_slot = give_item(&mut world, "box");
+        assert_eq!(
+            world.check_command(&Command::PutIn(box_slot, bag_slot)),
+            Err(ActionError::CannotContain)
+        );
+        let sword_slot = give_item(&mut world, "sword");
+        assert_eq!(
+            world.check_command(&Command::PutIn(box_slot, sword_slot)),
+            Err(ActionError::NotAContainer)
+        );
+        set_item_type(&mut world, bag_slot, ItemType::Container(1));
+        assert_eq!(
+            world.check_command(&Command::PutIn(sword_slot, bag_slot)),
+            Err(ActionError::ContainerFull)
+        );
+
+        wait_for_player(&mut world);
+        let slot = world.free_bag_slot(player).unwrap();
+        world.update(&Command::TakeOut(bag_slot, 0));
+        assert!(world.contents(bag).is_empty());
+        let potions = world.entity_equipped(player, slot).unwrap();
+        assert_eq!(world.quantity(potions), 2);
+        assert_eq!(
+            world.check_command(&Command::TakeOut(bag_slot, 0)),
+            Err(ActionError::NothingToTake)
+        );
+
+        // Destroyed containers spill their contents.
+        wait_for_player(&mut world);
+        world.update(&Command::PutIn(sword_slot, bag_slot));
+        let sword = world.contents(bag)[0];
+        wait_for_player(&mut world);
+        world.update(&Command::Drop(bag_slot, 1));
+        world.destroy_item(bag);
+        assert_eq!(world.location(bag), None);
+        assert!(world.location(sword).is_some());
+        assert!(world.entities_at(world.location(sword).unwrap()).contains(&sword));
+    }
+
+    #[test]
+    fn test_nutrition() {
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+        assert_eq!(world.hunger(player), Some(Hunger::Satiated));
+
+        world.ecs_mut().nutrition[player].satiation = 800;
+        world.update(&Command::Pass);
+        assert_eq!(world.hunger(player), Some(Hunger::Hungry));
+        assert!(saw_message(&world, "You are getting hungry."));
+
+        // Weak from hunger, no resting it off.
+        wait_for_player(&mut world);
+        world.ecs_mut().nutrition[player].satiation = 200;
+        world.ecs_mut().health[player].wounds = 5;
+        world.update(&Command::Pass);
+        assert_eq!(world.hunger(player), Some(Hunger::Weak));
+        assert_eq!(world.ecs().health[player].wounds, 5);
+        // Magic doesn't help either.
+        assert_eq!(world.tick_regeneration(player), None);
+
+        // Eating one item off a stack.
+        wait_for_player(&mut world);
+        let slot = give_item(&mut world, "apple");
+        let apples = world.entity_equipped(player, slot).unwrap();
+        world.ecs_mut().item[apples].quantity = 2;
+        world.update(&Command::UseItem(slot));
+        assert!(saw_message(&world, "You eat the apple."));
+        assert_eq!(world.quantity(apples), 1);
+        assert_eq!(world.ecs().nutrition[player].satiation, 200 - 1 + 400 - 1);
+
+        // Corpses are food too.
+        wait_for_player(&mut world);
+        set_item_type(&mut world, slot, ItemType::Corpse);
+        world.update(&Command::UseItem(slot));
+        assert_eq!(world.entity_equipped(player, slot), None);
+        assert_eq!(world.hunger(player), Some(Hunger::Satiated));
+
+        // An empty stomach hurts.
+        wait_for_player(&mut world);
+        world.ecs_mut().nutrition[player].satiation = 1;
+        world.ecs_mut().health[player].wounds = 0;
+        world.update(&Command::Pass);
+        assert_eq!(world.ecs().health[player].wounds, 1);
+    }
+
+    #[test]
+    fn test_experience() {
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+        world.ecs_mut().stats[player].base.power = 100;
+        world.rebuild_stats(player);
+        let power = world.base_stats(player).power;
+        assert_eq!(world.experience(player).unwrap().level, 1);
+
+        // Kills credited to the player give experience.
+        let (dreg, _) = spawn_next_to_player(&mut world, "dreg");
+        let xp = world.xp_value(dreg);
+        world.damage(dreg, 1000, Damage::Physical, Some(player));
+        assert!(!world.is_alive(dreg));
+        assert_eq!(world.experience(player).unwrap().xp, xp);
+
+        // Enough experience raises the level and base stats.
+        let next = world.experience(player).unwrap().next_level_xp();
+        world.gain_experience(player, next);
+        assert!(world.experience(player).unwrap().level >= 2);
+        assert!(saw_message(&world, "You are now level 2."));
+        assert!(world.events().iter().any(|e| match e {
+            Event::LevelUp { entity, level } => *entity == player && *level == 2,
+            _ => false,
+        }));
+        assert!(world.base_stats(player).power > power);
+        assert!(world.max_hp(player) > power);
+    }
+
+    #[test]
+    fn test_spells() {
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+        world.ecs_mut().stats[player].base.power = 100;
+        world.rebuild_stats(player);
+        let max_mana = world.max_mana(player);
+        assert!(max_mana > 0);
+        assert_eq!(world.mana(player), max_mana);
+
+        // Spells need to be learned before they can be cast.
+        let (dreg, dir) = spawn_next_to_player(&mut world, "dreg");
+        let slot = give_item(&mut world, "spellbook of lightning");
+        assert_eq!(world.check_command(&Command::CastAt(slot, dir)), Err(ActionError::NotUsable));
+        world.update(&Command::Equip(slot));
+        let spell = world.entity_equipped(player, Slot::Spell1).unwrap();
+        assert_eq!(world.check_command(&Command::Cast(Slot::Spell1)), Err(ActionError::NotUsable));
+
+        wait_for_player(&mut world);
+        world.update(&Command::CastAt(Slot::Spell1, dir));
+        assert!(!world.is_alive(dreg));
+        assert_eq!(world.mana(player), max_mana - world.spell(spell).unwrap().mana_cost);
+
+        wait_for_player(&mut world);
+        assert!(world.cooldown_left(spell) > 0);
+        assert_eq!(
+            world.check_command(&Command::CastAt(Slot::Spell1, dir)),
+            Err(ActionError::Cooldown)
+        );
+
+        // Mana comes back over time.
+        world.ecs_mut().mana[player].points = 0;
+        while world.cooldown_left(spell) > 0 {
+            let cmd = if world.player_can_act() { Command::Pass } else { Command::Wait };
+            world.update(&cmd);
+        }
+        wait_for_player(&mut world);
+        assert!(world.mana(player) > 0);
+        assert_eq!(
+            world.check_command(&Command::CastAt(Slot::Spell1, dir)),
+            Err(ActionError::NoMana)
+        );
+
+        // Untargeted spells work on the caster.
+        let slot = give_item(&mut world, "spellbook of healing");
+        world.update(&Command::Equip(slot));
+        wait_for_player(&mut world);
+        world.ecs_mut().mana[player].points = max_mana;
+        world.ecs_mut().health[player].wounds = 5;
+        world.update(&Command::Cast(Slot::Spell2));
+        assert_eq!(world.ecs().health[player].wounds, 0);
     }
 }
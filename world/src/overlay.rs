@@ -0,0 +1,50 @@
+use crate::location::{Location, Portal};
+use crate::terrain::Terrain;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Runtime changes to the generated game world.
+///
+/// `Worldgen` is regenerated from the seed when a game is loaded, so any terrain or portal that
+/// changes during play must be stored here to persist. Lookups should check the overlay first and
+/// fall back to the generated world.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Overlay {
+    terrain: BTreeMap<Location, Terrain>,
+    /// Changed portals. A `None` value marks a generated portal that has been removed.
+    portals: BTreeMap<Location, Option<Portal>>,
+}
+
+impl Overlay {
+    pub fn new() -> Overlay { Default::default() }
+
+    /// Return overlay terrain at location if it differs from the generated world.
+    pub fn terrain(&self, loc: Location) -> Option<Terrain> { self.terrain.get(&loc).cloned() }
+
+    pub fn set_terrain(&mut self, loc: Location, terrain: Terrain) {
+        self.terrain.insert(loc, terrain);
+    }
+
+    /// Remove terrain change at location, the generated terrain will show through.
+    pub fn clear_terrain(&mut self, loc: Location) { self.terrain.remove(&loc); }
+
+    /// Return portal change at location.
+    ///
+    /// The outer option is `None` if the portal at location has not changed, the inner option is
+    /// `None` if the portal was removed.
+    pub fn portal(&self, loc: Location) -> Option<Option<Portal>> {
+        self.portals.get(&loc).cloned()
+    }
+
+    pub fn set_portal(&mut self, loc: Location, portal: Option<Portal>) {
+        self.portals.insert(loc, portal);
+    }
+
+    /// Remove portal change at location, the generated portal (if any) will show through.
+    pub fn clear_portal(&mut self, loc: Location) { self.portals.remove(&loc); }
+
+    /// Return whether anything at location has been changed.
+    pub fn contains(&self, loc: Location) -> bool {
+        self.terrain.contains_key(&loc) || self.portals.contains_key(&loc)
+    }
+}
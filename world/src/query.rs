@@ -1,6 +1,10 @@
-use crate::components::{Alignment, BrainState, Icon, Status};
+use crate::command::{ActionError, ActionOutcome};
+use crate::components::{
+    Alignment, BrainState, Experience, Hunger, Icon, Requirements, Status,
+};
+use crate::effect::{Aura, Damage, OnHit};
 use crate::grammar::{Noun, Pronoun};
-use crate::item::{EquipType, ItemType, Slot};
+use crate::item::{EquipType, ItemClass, ItemType, Slot, Spell};
 use crate::location::Location;
 use crate::mapsave;
 use crate::spec::EntitySpawn;
@@ -10,15 +14,20 @@ use crate::terrain::Terrain;
 use crate::volume::Volume;
 use crate::world::Ecs;
 use crate::FovStatus;
-use calx::{clamp, hex_neighbors, CellVector, Dir6, HexGeom, Noise};
+use calx::{astar_path, clamp, hex_neighbors, CellVector, Dijkstra, Dir6, HexGeom, Noise};
 use calx_ecs::Entity;
 use euclid::vec2;
 use rand::distributions::Uniform;
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::iter::FromIterator;
 use std::slice;
 use std::str::FromStr;
 
+/// How many frames a regular action takes at normal speed.
+const BASE_ACTION_DELAY: u32 = 12;
+/// Lowest speed percentage, no matter how many slowing effects pile up.
+const MIN_SPEED: i32 = 10;
+
 /// Immutable querying of game world state.
 pub trait Query: TerrainQuery + Sized {
     /// Return the location of an entity.
@@ -33,9 +42,18 @@ pub trait Query: TerrainQuery + Sized {
     /// Return current time of the world logic clock.
     fn get_tick(&self) -> u64;
 
+    /// Return the amount of gold the player has collected.
+    fn gold(&self) -> u32;
+
     /// Return world RNG seed
     fn rng_seed(&self) -> u32;
 
+    /// Return whether the player has identified the item kind with the given true name.
+    fn is_identified_kind(&self, name: &str) -> bool;
+
+    /// Return the unidentified name and icon of the item kind with the given true name.
+    fn kind_appearance(&self, name: &str) -> Option<(String, Icon)>;
+
     /// Return maximum health of an entity.
     fn max_hp(&self, e: Entity) -> i32 { self.stats(e).power }
 
@@ -104,13 +122,55 @@ pub trait Query: TerrainQuery + Sized {
     }
 
     /// Return visual brush for an entity.
-    fn entity_icon(&self, e: Entity) -> Option<Icon> { self.ecs().desc.get(e).map(|x| x.icon) }
+    ///
+    /// Unidentified items show their randomized appearance.
+    fn entity_icon(&self, e: Entity) -> Option<Icon> {
+        if !self.is_identified(e) {
+            if let Some((_, icon)) = self.appearance(e) {
+                return Some(icon);
+            }
+        }
+        self.ecs().desc.get(e).map(|x| x.icon)
+    }
 
+    /// Return the name of an entity as the player knows it.
     fn entity_name(&self, e: Entity) -> String {
+        if !self.is_identified(e) {
+            if let Some((name, _)) = self.appearance(e) {
+                return name;
+            }
+        }
+        self.true_name(e)
+    }
+
+    /// Return the actual name of an entity, whether the player knows it or not.
+    fn true_name(&self, e: Entity) -> String {
         self.ecs()
             .desc
             .get(e)
-            .map_or_else(|| "N/A".to_string(), |x| x.name.clone())
+            .map_or_else(|| "N/A".to_string(), |x| x.full_name())
+    }
+
+    /// Return the class of look-alike magic items the entity belongs to.
+    fn item_class(&self, e: Entity) -> Option<ItemClass> {
+        self.ecs().desc.get(e).and_then(|x| ItemClass::from_icon(x.icon))
+    }
+
+    /// Return whether the player knows what an entity is.
+    ///
+    /// Only potions, scrolls and wands need to be identified.
+    fn is_identified(&self, e: Entity) -> bool {
+        self.item_class(e).is_none()
+            || self.spawn_name(e).map_or(false, |n| self.is_identified_kind(n))
+    }
+
+    /// Return the name and icon an item kind has in this game before it's identified.
+    ///
+    /// Appearances are shuffled among the kinds of each item class using the world seed, so
+    /// they stay the same through the game but differ between games.
+    fn appearance(&self, e: Entity) -> Option<(String, Icon)> {
+        self.item_class(e)?;
+        self.kind_appearance(self.spawn_name(e)?)
     }
 
     fn noun(&self, e: Entity) -> Noun {
@@ -118,6 +178,10 @@ pub trait Query: TerrainQuery + Sized {
         if self.is_player(e) {
             ret = ret.you().pronoun(Pronoun::They);
         }
+        let quantity = self.quantity(e);
+        if quantity > 1 {
+            ret = ret.count(quantity);
+        }
         // TODO: Human mobs get he/she pronoun instead of it.
         ret
     }
@@ -143,8 +207,30 @@ pub trait Query: TerrainQuery + Sized {
 
     /// Return whether the entity can move in a direction.
     fn can_step(&self, e: Entity, dir: Dir6) -> bool {
-        self.location(e)
-            .map_or(false, |loc| self.can_enter(e, loc.jump(self, dir)))
+        let destination = match self.location(e) {
+            Some(loc) => loc.jump(self, dir),
+            None => return false,
+        };
+
+        if self.can_enter(e, destination) {
+            return true;
+        }
+
+        // Friendly mobs in the way can be displaced.
+        match self.mob_at(destination) {
+            Some(other) => self.can_displace(e, other),
+            None => false,
+        }
+    }
+
+    /// Return whether the entity can step into the other one's place and swap positions.
+    ///
+    /// The player can swap places with friendly mobs.
+    fn can_displace(&self, e: Entity, other: Entity) -> bool {
+        self.is_player(e)
+            && self.is_npc(other)
+            && !self.is_hostile_to(e, other)
+            && !self.is_hostile_to(other, e)
     }
 
     /// Return whether location blocks line of sight.
@@ -214,6 +300,22 @@ pub trait Query: TerrainQuery + Sized {
         self.stats(e).intrinsics & (1 << intrinsic as u32) != 0
     }
 
+    /// Return whether the entity takes reduced damage of the given type.
+    fn resists(&self, e: Entity, damage: Damage) -> bool {
+        match damage.resistance() {
+            Some(intrinsic) => self.has_intrinsic(e, intrinsic),
+            None => false,
+        }
+    }
+
+    /// Return whether the entity takes extra damage of the given type.
+    fn is_vulnerable_to(&self, e: Entity, damage: Damage) -> bool {
+        match damage.vulnerability() {
+            Some(intrinsic) => self.has_intrinsic(e, intrinsic),
+            None => false,
+        }
+    }
+
     /// Return whether the entity has a specific temporary status
     fn has_status(&self, e: Entity, status: Status) -> bool {
         self.ecs()
@@ -222,37 +324,109 @@ pub trait Query: TerrainQuery + Sized {
             .map_or(false, |s| s.contains_key(&status))
     }
 
-    /// Return how many frames the entity will delay after an action.
-    fn action_delay(&self, e: Entity) -> u32 {
-        // Granular speed system:
-        // | slow and slowed  | 1 |
-        // | slow or slowed   | 2 |
-        // | normal           | 3 |
-        // | quick or hasted  | 4 |
-        // | quick and hasted | 5 |
-
-        let mut speed = 3;
+    /// Return whether the entity is unable to act because of a status.
+    fn is_incapacitated(&self, e: Entity) -> bool {
+        self.has_status(e, Status::Asleep) || self.has_status(e, Status::Paralyzed)
+    }
+
+    /// Return whether hostile creatures are unable to notice the entity.
+    fn is_invisible(&self, e: Entity) -> bool { self.has_status(e, Status::Invisible) }
+
+    /// Return whether an invisible target escapes the viewer's notice.
+    fn is_hidden_from(&self, viewer: Entity, target: Entity) -> bool {
+        self.is_invisible(target) && !self.has_status(viewer, Status::SeeInvisible)
+    }
+
+    /// Return the items currently equipped by an entity.
+    fn equipped_items(&self, e: Entity) -> Vec<Entity> {
+        Slot::equipped_iter()
+            .filter_map(|&slot| self.entity_equipped(e, slot))
+            .collect()
+    }
+
+    /// Return the on-hit effects of an entity's equipment.
+    fn on_hit_effects(&self, e: Entity) -> Vec<OnHit> {
+        self.equipped_items(e)
+            .into_iter()
+            .filter_map(|item| self.ecs().item.get(item))
+            .flat_map(|item| item.on_hit.iter().cloned())
+            .collect()
+    }
+
+    /// Return the passive auras of an entity's equipment.
+    fn auras(&self, e: Entity) -> Vec<Aura> {
+        self.equipped_items(e)
+            .into_iter()
+            .filter_map(|item| self.ecs().item.get(item))
+            .flat_map(|item| item.auras.iter().cloned())
+            .collect()
+    }
+
+    /// Return how much equipped light sources extend the entity's sight.
+    fn light_radius(&self, e: Entity) -> u32 {
+        self.auras(e)
+            .into_iter()
+            .map(|aura| match aura {
+                Aura::Light(radius) => radius,
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Return the entity's speed as a percentage of normal speed.
+    fn speed(&self, e: Entity) -> u32 {
+        // Each speed intrinsic and status makes the creature a third faster or slower.
+        let mut thirds = 3;
         if self.has_intrinsic(e, Intrinsic::Slow) {
-            speed -= 1;
+            thirds -= 1;
         }
         if self.has_status(e, Status::Slowed) {
-            speed -= 1;
+            thirds -= 1;
         }
         if self.has_intrinsic(e, Intrinsic::Quick) {
-            speed += 1;
+            thirds += 1;
         }
         if self.has_status(e, Status::Hasted) {
-            speed += 1;
+            thirds += 1;
         }
 
-        match speed {
-            1 => 36,
-            2 => 18,
-            3 => 12,
-            4 => 9,
-            5 => 7,
-            _ => panic!("Invalid speed value {}", speed),
+        let speed = 100 * thirds / 3 + self.stats(e).speed;
+        speed.max(MIN_SPEED) as u32
+    }
+
+    /// Return how many frames the entity will delay after an action.
+    ///
+    /// The cost of the action is a percentage of the cost of a regular action like taking a step.
+    fn action_delay(&self, e: Entity, cost: u32) -> u32 {
+        let frames = BASE_ACTION_DELAY * cost;
+        let speed = self.speed(e);
+        // Round to the nearest frame.
+        ((frames * 2 + speed) / (speed * 2)).max(1)
+    }
+
+    /// Return the world tick on which the entity gets to act next.
+    fn next_action_time(&self, e: Entity) -> u64 {
+        self.ecs().schedule.get(e).map_or(0, |s| s.next_action)
+    }
+
+    /// Return how many world updates it will take until the player gets to act again.
+    ///
+    /// Returns `None` if there is no player. Statuses that stop the player from acting are
+    /// accounted for, but the player might still be interrupted early, for example by being woken
+    /// up.
+    fn ticks_until_player_acts(&self) -> Option<u64> {
+        let player = self.player()?;
+        let tick = self.get_tick();
+        let mut ready = self.next_action_time(player);
+        if let Some(statuses) = self.ecs().status.get(player) {
+            for status in &[Status::Asleep, Status::Paralyzed] {
+                if let Some(&duration) = statuses.get(status) {
+                    ready = ready.max(tick + u64::from(duration));
+                }
+            }
         }
+        Some(ready.saturating_sub(tick))
     }
 
     /// Return if the entity is a mob that should get an update this frame
@@ -263,11 +437,7 @@ pub trait Query: TerrainQuery + Sized {
             return false;
         }
 
-        if self.has_status(e, Status::Delayed) {
-            return false;
-        }
-
-        true
+        self.next_action_time(e) <= self.get_tick()
     }
 
     /// Return whether the entity is dead and should be removed from the world.
@@ -297,7 +467,7 @@ pub trait Query: TerrainQuery + Sized {
 
     /// Return whether the entity is a mob that will act this frame.
     fn acts_this_frame(&self, e: Entity) -> bool {
-        if !self.is_active(e) {
+        if !self.is_active(e) || self.is_incapacitated(e) {
             return false;
         }
         self.ticks_this_frame(e)
@@ -351,6 +521,216 @@ pub trait Query: TerrainQuery + Sized {
         None
     }
 
+    /// Return whether a mob is hurt badly enough to want to run away.
+    fn is_demoralized(&self, e: Entity) -> bool {
+        let morale = match self.ecs().brain.get(e) {
+            Some(brain) => brain.morale as i32,
+            None => return false,
+        };
+        let wounds = self.max_hp(e) - self.hp(e);
+        wounds * 100 > self.max_hp(e) * morale
+    }
+
+    /// Return the direction to run away from a threat.
+    ///
+    /// Uses an inverted safety map, so fleeing will prefer open space over dead ends. Returns
+    /// `None` if the entity is cornered.
+    fn flee_dir(&self, e: Entity, threat: Location) -> Option<Dir6> {
+        const FLEE_RANGE: u32 = 24;
+
+        let origin = self.location(e)?;
+        let neighbors = |&loc: &Location| -> Vec<Location> {
+            Dir6::iter()
+                .map(|&d| loc.jump(self, d))
+                .filter(|&n| !self.terrain(n).blocks_walk())
+                .collect()
+        };
+
+        let map = Dijkstra::new_with_neighbors(vec![threat], neighbors, FLEE_RANGE);
+        let map = map.inverted(neighbors);
+
+        let current = *map.weights.get(&origin)?;
+        Dir6::iter()
+            .filter(|&&d| self.can_step(e, d))
+            .filter_map(|&d| map.weights.get(&origin.jump(self, d)).map(|&w| (w, d)))
+            .filter(|&(w, _)| w < current)
+            .min_by_key(|&(w, _)| w)
+            .map(|(_, d)| d)
+    }
+
+    /// Return the other mobs within range that have a clear line of sight to the entity.
+    fn mobs_in_view(&self, e: Entity, range: u32) -> Vec<Entity> {
+        let origin = match self.location(e) {
+            Some(loc) => loc,
+            None => return Vec::new(),
+        };
+
+        self.sphere_volume(origin, range)
+            .0
+            .into_iter()
+            .flat_map(|loc| self.entities_at(loc))
+            .filter(|&m| m != e && self.is_mob(m))
+            .collect()
+    }
+
+    /// Return the closest hostile mob in view within range.
+    fn nearest_hostile(&self, e: Entity, range: u32) -> Option<Entity> {
+        let origin = self.location(e)?;
+        self.mobs_in_view(e, range)
+            .into_iter()
+            .filter(|&m| self.is_hostile_to(e, m) && !self.is_hidden_from(e, m))
+            .filter_map(|m| self.location(m).map(|loc| (loc.metric_distance(origin), m)))
+            .min_by_key(|&(dist, _)| dist)
+            .map(|(_, m)| m)
+    }
+
+    /// Return hostile mobs the entity can currently see.
+    ///
+    /// Only entities with a map memory can see anything.
+    fn visible_hostiles(&self, e: Entity) -> Vec<Entity> {
+        let memory = match self.ecs().map_memory.get(e) {
+            Some(memory) => memory,
+            None => return Vec::new(),
+        };
+
+        self.active_mobs()
+            .into_iter()
+            .filter(|&m| self.is_hostile_to(e, m) && !self.is_hidden_from(e, m))
+            .filter(|&m| match self.location(m) {
+                Some(loc) => memory.seen.contains(&loc),
+                None => false,
+            })
+            .collect()
+    }
+
+    /// Return the direction of the next step for exploring the unknown parts of the map.
+    ///
+    /// The explorer heads for the nearest remembered location next to unexplored space, preferring
+    /// unexplored space on the same level over space beyond portals. If `take_items` is set and
+    /// there is room in the bag, remembered item locations also attract the explorer.
+    ///
+    /// Returns `None` if there is nothing reachable left to explore.
+    fn explore_dir(&self, e: Entity, take_items: bool) -> Option<Dir6> {
+        const MAX_DISTANCE: u32 = 1024;
+
+        let origin = self.location(e)?;
+        let memory = self.ecs().map_memory.get(e)?;
+
+        // Only look at the terrain of remembered cells, unseen terrain must stay unknown.
+        let is_open = |loc: Location| !self.terrain(loc).blocks_walk();
+        let is_unexplored = |loc: Location| !memory.remembered.contains(&loc);
+        let neighbors = |loc: Location| Dir6::iter().map(move |&d| loc.jump(self, d));
+
+        let explored: Vec<Location> = memory.remembered.iter().filter(|&l| is_open(l)).collect();
+        let frontier = |same_level: bool| -> Vec<Location> {
+            explored
+                .iter()
+                .cloned()
+                .filter(|&loc| {
+                    // Map memory doesn't extend past the current sector, so other sectors only
+                    // count when reached through a portal.
+                    neighbors(loc).any(|n| {
+                        is_unexplored(n)
+                            && if same_level { n.sector() == loc.sector() } else { n.z != loc.z }
+                    })
+                })
+                .collect()
+        };
+
+        let mut goals = frontier(true);
+        if goals.is_empty() {
+            goals = frontier(false);
+        }
+
+        if take_items && self.free_bag_slot(e).is_some() {
+            goals.extend(
+                explored
+                    .iter()
+                    .filter(|&&loc| loc != origin && self.item_at(loc).is_some()),
+            );
+        }
+
+        if goals.is_empty() {
+            return None;
+        }
+
+        if goals.contains(&origin) {
+            // Standing at the edge of the known, step out into the unexplored space.
+            if let Some(&dir) = Dir6::iter()
+                .find(|&&d| is_unexplored(origin.jump(self, d)) && self.can_step(e, d))
+            {
+                return Some(dir);
+            }
+            // The unexplored cells here can't be entered, look for another way out.
+            goals.retain(|&loc| loc != origin);
+            if goals.is_empty() {
+                return None;
+            }
+        }
+
+        // Portals only go one way, so distances to the goals follow the steps leading into cells.
+        let mut step_sources: HashMap<Location, Vec<Location>> = HashMap::new();
+        for &loc in &explored {
+            for n in neighbors(loc).filter(|&n| memory.remembered.contains(&n) && is_open(n)) {
+                step_sources.entry(n).or_default().push(loc);
+            }
+        }
+        let map = Dijkstra::new_with_neighbors(
+            goals,
+            |loc| step_sources.get(loc).cloned().unwrap_or_default(),
+            MAX_DISTANCE,
+        );
+
+        let current = *map.weights.get(&origin)?;
+        Dir6::iter()
+            .filter(|&&d| self.can_step(e, d))
+            .filter_map(|&d| map.weights.get(&origin.jump(self, d)).map(|&w| (w, d)))
+            .filter(|&(w, _)| w < current)
+            .min_by_key(|&(w, _)| w)
+            .map(|(_, d)| d)
+    }
+
+    /// Return the direction of the next step on the shortest path to destination.
+    ///
+    /// The path follows portals and only goes through remembered locations the entity can enter.
+    /// A destination with a portal is treated as the far end of the portal. The destination
+    /// itself doesn't need to be remembered, so the path can lead through unexplored stairs.
+    ///
+    /// Returns `None` if there is no known path or the entity is already at destination.
+    fn travel_dir(&self, e: Entity, destination: Location) -> Option<Dir6> {
+        let origin = self.location(e)?;
+        let memory = self.ecs().map_memory.get(e)?;
+        let destination = self.portal(destination).unwrap_or(destination);
+
+        // Portals can make the straight-line distance overestimate the remaining path, so search
+        // without a distance heuristic to keep the path through them the shortest one.
+        let path = astar_path(origin, &destination, |&loc| {
+            Dir6::iter()
+                .map(|&d| loc.jump(self, d))
+                .filter(|&n| n == destination || memory.remembered.contains(&n))
+                .filter(|&n| self.can_enter(e, n))
+                .map(|n| (n, 0.0))
+                .collect()
+        })?;
+
+        let next = *path.get(1)?;
+        Dir6::iter().find(|&&d| origin.jump(self, d) == next).cloned()
+    }
+
+    /// Return the nearest remembered location with the given terrain on the entity's level.
+    fn nearest_remembered(&self, e: Entity, terrain: Terrain) -> Option<Location> {
+        let origin = self.location(e)?;
+        let memory = self.ecs().map_memory.get(e)?;
+
+        memory
+            .remembered
+            .iter()
+            .filter(|&loc| self.terrain(loc) == terrain)
+            .filter_map(|loc| loc.distance_from(origin).map(|d| (d, loc)))
+            .min_by_key(|&(d, _)| d)
+            .map(|(_, loc)| loc)
+    }
+
     /// Return whether the entity wants to fight the other entity.
     fn is_hostile_to(&self, e: Entity, other: Entity) -> bool {
         let (a, b) = (self.alignment(e), self.alignment(other));
@@ -437,7 +817,7 @@ pub trait Query: TerrainQuery + Sized {
             map.push((pos, (terrain, entities)));
         }
 
-        mapsave::Prefab::from_iter(map.into_iter())
+        mapsave::Prefab::from_iter(map)
     }
 
     fn free_bag_slot(&self, e: Entity) -> Option<Slot> {
@@ -446,14 +826,38 @@ pub trait Query: TerrainQuery + Sized {
             .cloned()
     }
 
-    fn free_equip_slot(&self, e: Entity, item: Entity) -> Option<Slot> {
-        if let Some(equip_type) = self.equip_type(item) {
-            Slot::iter()
-                .find(|&&x| x.accepts(equip_type) && self.entity_equipped(e, x).is_none())
-                .cloned()
-        } else {
-            None
+    /// Find a slot to equip an item in.
+    ///
+    /// Fails with the reason if the entity can't equip the item.
+    fn free_equip_slot(&self, e: Entity, item: Entity) -> Result<Slot, ActionError> {
+        let equip_type = self.equip_type(item).ok_or(ActionError::CannotEquip)?;
+        self.check_requirements(e, item)?;
+        Slot::iter()
+            .find(|&&x| x.accepts(equip_type) && self.entity_equipped(e, x).is_none())
+            .cloned()
+            .ok_or(ActionError::NoFreeSlot)
+    }
+
+    /// Return what it takes to equip an item.
+    fn requirements(&self, item: Entity) -> Requirements {
+        self.ecs().item.get(item).map_or_else(Default::default, |i| i.requirements)
+    }
+
+    /// Check whether the entity meets the requirements for equipping an item.
+    fn check_requirements(&self, e: Entity, item: Entity) -> ActionOutcome {
+        let requirements = self.requirements(item);
+        if requirements.hands && !self.has_intrinsic(e, Intrinsic::Hands) {
+            return Err(ActionError::NoHands);
         }
+        if self.stats(e).power < requirements.min_power {
+            return Err(ActionError::TooWeak);
+        }
+        Ok(())
+    }
+
+    /// Return whether an item is cursed and can't be unequipped.
+    fn is_cursed(&self, item: Entity) -> bool {
+        self.ecs().item.get(item).map_or(false, |i| i.cursed)
     }
 
     /// Find a drop position for an item, trying to keep one item per cell.
@@ -518,6 +922,14 @@ pub trait Query: TerrainQuery + Sized {
     /// Return whether the player can currently directly see the given location.
     fn player_sees(&self, loc: Location) -> bool { self.fov_status(loc) == Some(FovStatus::Seen) }
 
+    /// Return whether the player can see the entity.
+    fn player_sees_entity(&self, e: Entity) -> bool {
+        match self.location(e) {
+            Some(loc) => self.player_sees(loc),
+            None => false,
+        }
+    }
+
     /// Return the set of mobs that are in update range.
     ///
     /// In a large game world, the active set is limited to the player's surroundings.
@@ -531,6 +943,134 @@ pub trait Query: TerrainQuery + Sized {
     /// Return number of times item can be used.
     fn uses_left(&self, item: Entity) -> u32 { self.ecs().item.get(item).map_or(0, |i| i.charges) }
 
+    /// Return how many items there are in an item stack.
+    fn quantity(&self, item: Entity) -> u32 { self.ecs().item.get(item).map_or(1, |i| i.quantity) }
+
+    /// Return whether several copies of an item can share one inventory slot.
+    ///
+    /// Consumables stack, equipment and wands that keep track of their own charges don't.
+    fn is_stackable(&self, item: Entity) -> bool {
+        self.destroy_after_use(item) || matches!(self.item_type(item), Some(ItemType::Food(_)))
+    }
+
+    /// Return how much nutrition eating the item gives, or None if it isn't edible.
+    fn nutrition_value(&self, item: Entity) -> Option<u32> {
+        const CORPSE_NUTRITION: u32 = 800;
+
+        match self.item_type(item) {
+            Some(ItemType::Food(amount)) => Some(amount),
+            Some(ItemType::Corpse) => Some(CORPSE_NUTRITION),
+            _ => None,
+        }
+    }
+
+    /// Return the spell an item teaches.
+    fn spell(&self, item: Entity) -> Option<Spell> {
+        match self.item_type(item) {
+            Some(ItemType::Spell(spell)) => Some(spell),
+            _ => None,
+        }
+    }
+
+    /// Return the mana the entity has left.
+    fn mana(&self, e: Entity) -> i32 { self.ecs().mana.get(e).map_or(0, |m| m.points) }
+
+    fn max_mana(&self, e: Entity) -> i32 { self.stats(e).mana }
+
+    /// Return how many frames are left before a spell can be cast again.
+    fn cooldown_left(&self, spell: Entity) -> u64 {
+        self.ecs()
+            .cooldown
+            .get(spell)
+            .map_or(0, |c| c.ready_at.saturating_sub(self.get_tick()))
+    }
+
+    /// Check whether the entity can cast a spell right now.
+    fn check_cast(&self, e: Entity, spell: Entity) -> ActionOutcome {
+        let mana_cost = self.spell(spell).ok_or(ActionError::NotUsable)?.mana_cost;
+        if self.cooldown_left(spell) > 0 {
+            return Err(ActionError::Cooldown);
+        }
+        if self.mana(e) < mana_cost {
+            return Err(ActionError::NoMana);
+        }
+        Ok(())
+    }
+
+    /// Return the entity's experience, or None if it doesn't gain levels.
+    fn experience(&self, e: Entity) -> Option<Experience> { self.ecs().experience.get(e).cloned() }
+
+    /// Return how much experience killing the entity is worth.
+    fn xp_value(&self, e: Entity) -> u32 { self.base_stats(e).power.max(1) as u32 }
+
+    /// Return how hungry the entity is, or None if it doesn't need to eat.
+    fn hunger(&self, e: Entity) -> Option<Hunger> {
+        self.ecs().nutrition.get(e).map(|n| n.hunger())
+    }
+
+    /// Return whether two items are interchangeable and can be merged into one stack.
+    fn can_stack(&self, stack: Entity, item: Entity) -> bool {
+        if stack == item || !self.is_stackable(stack) {
+            return false;
+        }
+        match (self.ecs().item.get(stack), self.ecs().item.get(item)) {
+            (Some(a), Some(b)) => {
+                a.item_type == b.item_type
+                    && a.charges == b.charges
+                    && a.cursed == b.cursed
+                    && a.enchantment == b.enchantment
+                    && a.affix == b.affix
+                    && self.spawn_name(stack) == self.spawn_name(item)
+            }
+            _ => false,
+        }
+    }
+
+    /// Return the slot of a stack in the entity's inventory that the item can be merged into.
+    fn stack_slot(&self, e: Entity, item: Entity) -> Option<Slot> {
+        Slot::iter()
+            .find(|&&slot| self.entity_equipped(e, slot).map_or(false, |s| self.can_stack(s, item)))
+            .cloned()
+    }
+
+    /// Return whether the item can hold other items.
+    fn is_container(&self, item: Entity) -> bool { self.container_capacity(item) > 0 }
+
+    /// Return how many items fit in a container.
+    fn container_capacity(&self, item: Entity) -> usize {
+        match self.item_type(item) {
+            Some(ItemType::Container(capacity)) => capacity as usize,
+            _ => 0,
+        }
+    }
+
+    /// Return the items inside a container.
+    fn contents(&self, container: Entity) -> Vec<Entity> { self.entities_in(container) }
+
+    /// Return a stack inside the container that the item can be merged into.
+    fn container_stack(&self, container: Entity, item: Entity) -> Option<Entity> {
+        self.contents(container)
+            .into_iter()
+            .find(|&stack| self.can_stack(stack, item))
+    }
+
+    /// Check whether an item can be put inside a container.
+    fn check_stash(&self, container: Entity, item: Entity) -> ActionOutcome {
+        if !self.is_container(container) {
+            return Err(ActionError::NotAContainer);
+        }
+        // Containers don't nest.
+        if self.is_container(item) {
+            return Err(ActionError::CannotContain);
+        }
+        if self.container_stack(container, item).is_none()
+            && self.contents(container).len() >= self.container_capacity(container)
+        {
+            return Err(ActionError::ContainerFull);
+        }
+        Ok(())
+    }
+
     fn destroy_after_use(&self, item: Entity) -> bool {
         // XXX: Fragile. What we want here is to tag potions and scrolls as destroyed when used and
         // wands to stick around. Current item data doesn't have is_potion or is_scroll, but
@@ -546,17 +1086,7 @@ pub trait Query: TerrainQuery + Sized {
     }
 
     fn equip_type(&self, item: Entity) -> Option<EquipType> {
-        use crate::ItemType::*;
-        match self.item_type(item) {
-            Some(MeleeWeapon) => Some(EquipType::Melee),
-            Some(RangedWeapon) => Some(EquipType::Ranged),
-            Some(Helmet) => Some(EquipType::Head),
-            Some(Armor) => Some(EquipType::Body),
-            Some(Boots) => Some(EquipType::Feet),
-            Some(Spell) => Some(EquipType::Spell),
-            Some(Trinket) => Some(EquipType::Trinket),
-            _ => None,
-        }
+        self.item_type(item).and_then(|t| t.equip_type())
     }
 
     fn is_underground(&self, loc: Location) -> bool { loc.z > 0 }
@@ -584,3 +1114,137 @@ pub trait Query: TerrainQuery + Sized {
         1.0
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Query;
+    use crate::command::Command;
+    use crate::item::roll_appearances;
+    use crate::terrain::Terrain;
+    use crate::test_util::{
+        give_item, peaceful_world, saw_message, spawn_next_to_player, wait_for_player,
+    };
+    use crate::world::World;
+    use calx::Incremental;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_explore() {
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+        let start = world.location(player).unwrap();
+
+        for _ in 0..5000 {
+            let dir = world.explore_dir(player, false).expect("Ran out of exploration");
+            world.update(&Command::Step(dir));
+            wait_for_player(&mut world);
+
+            if world.location(player).unwrap().z != start.z {
+                break;
+            }
+        }
+
+        // Once the starting level is explored, the explorer moves on through the stairs.
+        assert_ne!(world.location(player).unwrap().z, start.z);
+    }
+
+    #[test]
+    fn test_travel_to_exit() {
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+        let start = world.location(player).unwrap();
+
+        // Explore until the exit is found.
+        let exit = loop {
+            if let Some(loc) = world.nearest_remembered(player, Terrain::Exit) {
+                break loc;
+            }
+            let dir = world.explore_dir(player, false).expect("Exit not found");
+            world.update(&Command::Step(dir));
+            wait_for_player(&mut world);
+        };
+
+        for _ in 0..1000 {
+            let dir = match world.travel_dir(player, exit) {
+                Some(dir) => dir,
+                None => break,
+            };
+            world.update(&Command::Step(dir));
+            wait_for_player(&mut world);
+        }
+
+        // Traveling to the exit takes you through it.
+        assert_eq!(world.location(player).unwrap().z, start.z + 1);
+    }
+
+    #[test]
+    fn test_identification() {
+        let appearances = |seed| {
+            let mut world = peaceful_world(seed);
+            let player = world.player().unwrap();
+            ["wand of fireball", "scroll of lightning", "potion of healing"]
+                .iter()
+                .map(|name| {
+                    let slot = give_item(&mut world, name);
+                    let item = world.entity_equipped(player, slot).unwrap();
+                    world.entity_name(item)
+                })
+                .collect::<Vec<String>>()
+        };
+        // Appearances stay put within a game but get reshuffled between games.
+        assert_eq!(appearances(1), appearances(1));
+        assert_ne!(appearances(1), appearances(2));
+
+        // No two kinds look the same.
+        let looks = roll_appearances(1);
+        let names: HashSet<&String> = looks.values().map(|(name, _)| name).collect();
+        assert_eq!(names.len(), looks.len());
+
+        // Appearances survive saving and loading.
+        let mut world = peaceful_world(1);
+        let slot = give_item(&mut world, "potion of healing");
+        let potion = world.entity_equipped(world.player().unwrap(), slot).unwrap();
+        let mut save = Vec::new();
+        world.save(&mut save).unwrap();
+        let loaded = World::load(&mut &save[..]).unwrap();
+        assert_eq!(loaded.entity_name(potion), world.entity_name(potion));
+
+        let mut world = peaceful_world(1);
+        let player = world.player().unwrap();
+        let slot = give_item(&mut world, "wand of confusion");
+        let wand = world.entity_equipped(player, slot).unwrap();
+        let unknown = world.entity_name(wand);
+        assert!(!world.is_identified(wand));
+        assert!(unknown.ends_with(" wand"));
+
+        // Using an item identifies its kind.
+        let (_, dir) = spawn_next_to_player(&mut world, "dreg");
+        world.update(&Command::Zap(slot, dir));
+        assert!(saw_message(
+            &world,
+            &format!("The {} is a wand of confusion.", unknown)
+        ));
+        assert_eq!(world.entity_name(wand), "wand of confusion");
+        let slot = give_item(&mut world, "wand of confusion");
+        let other_wand = world.entity_equipped(player, slot).unwrap();
+        assert!(world.is_identified(other_wand));
+
+        // A scroll of identify reveals everything in the inventory.
+        wait_for_player(&mut world);
+        let slot = give_item(&mut world, "potion of healing");
+        let potion = world.entity_equipped(player, slot).unwrap();
+        let slot = give_item(&mut world, "scroll of identify");
+        assert!(!world.is_identified(potion));
+        world.update(&Command::UseItem(slot));
+        assert!(world.is_identified(potion));
+        assert_eq!(world.entity_name(potion), "potion of healing");
+
+        // Identified kinds are remembered in saves.
+        let mut save = Vec::new();
+        world.save(&mut save).unwrap();
+        let world = World::load(&mut &save[..]).unwrap();
+        assert!(world.is_identified_kind("scroll of identify"));
+        assert!(world.is_identified_kind("wand of confusion"));
+        assert!(!world.is_identified_kind("scroll of lightning"));
+    }
+}
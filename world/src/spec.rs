@@ -1,15 +1,28 @@
+use crate::biome::{BiomeSpec, DUNGEON_BIOME};
 use crate::components::{
-    Anim, Brain, Desc, Health, Icon, Item, ShoutType, StatsComponent, Statuses,
+    Alignment, Anim, Brain, Desc, Health, Icon, Item, Requirements, Schedule, ShoutType,
+    StatsComponent, Statuses,
 };
-use crate::item::ItemType;
+use crate::effect::{Aura, OnHit};
+use crate::item::{Affix, EquipType, ItemClass, ItemType};
 use crate::stats::{Intrinsic, Stats};
 use crate::world::Loadout;
 use crate::{Distribution, Rng};
+use calx::RngExt;
 use lazy_static::lazy_static;
+use log::error;
+use rand::seq::SliceRandom;
+use rand::Rng as _;
 use serde;
-use std::collections::HashMap;
+use serde_derive::Deserialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
 use std::error::Error;
 use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
@@ -26,17 +39,41 @@ pub trait Spec: Distribution<Loadout> + Sync + Send {
     fn min_depth(&self) -> i32;
 
     fn name(&self) -> &str;
+
+    /// What does the entity look like?
+    fn icon(&self) -> Icon;
+
+    /// Generate the entity for a specific dungeon depth.
+    ///
+    /// Things found deeper down may come out stronger.
+    fn sample_at(&self, rng: &mut Rng, depth: i32) -> Loadout {
+        let _ = depth;
+        self.sample(rng)
+    }
+
+    /// What kind of item does the spec make, if any?
+    fn item_type(&self) -> Option<ItemType> { None }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct MobSpec {
     name: String,
+    #[serde(deserialize_with = "de_icon")]
     icon: Icon,
     depth: i32,
     rarity: f32,
     power: i32,
+    /// Natural armor that absorbs physical damage before wounds.
+    armor: i32,
+    /// Size of the mana pool for casting spells.
+    mana: i32,
+    #[serde(deserialize_with = "de_intrinsics")]
     intrinsics: Vec<Intrinsic>,
     shout: ShoutType,
+    alignment: Alignment,
+    /// Percentage of health lost before the mob flees, 100 for fearless mobs.
+    morale: u32,
 }
 
 impl Default for MobSpec {
@@ -47,8 +84,12 @@ impl Default for MobSpec {
             depth: 0,
             rarity: 1.0,
             power: 0,
+            armor: 0,
+            mana: 0,
             intrinsics: Vec::new(),
             shout: ShoutType::Silent,
+            alignment: Alignment::Evil,
+            morale: 100,
         }
     }
 }
@@ -56,15 +97,25 @@ impl Default for MobSpec {
 impl Distribution<Loadout> for MobSpec {
     fn sample(&self, _: &mut Rng) -> Loadout {
         Loadout::new()
-            .c(StatsComponent::new(Stats::new(
-                self.power,
-                &self.intrinsics,
-            )))
+            .c(StatsComponent::new(
+                Stats::new(self.power, &self.intrinsics)
+                    .armor(self.armor)
+                    .mana(self.mana),
+            ))
             .c(Desc::new(&self.name, self.icon))
-            .c(Brain::enemy())
+            .c(Brain {
+                shout: self.shout,
+                alignment: self.alignment,
+                morale: self.morale,
+                ..Brain::enemy()
+            })
             .c(Anim::default())
-            .c(Health::default())
+            .c(Health {
+                armor: self.armor,
+                ..Health::default()
+            })
             .c(Statuses::default())
+            .c(Schedule::default())
     }
 }
 
@@ -72,11 +123,14 @@ impl Spec for MobSpec {
     fn rarity(&self) -> f32 { self.rarity }
     fn min_depth(&self) -> i32 { self.depth }
     fn name(&self) -> &str { &self.name }
+    fn icon(&self) -> Icon { self.icon }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ItemSpec {
     name: String,
+    #[serde(deserialize_with = "de_icon")]
     icon: Icon,
     depth: i32,
     rarity: f32,
@@ -85,7 +139,18 @@ pub struct ItemSpec {
     armor: i32,
     attack: i32,
     defense: i32,
+    ranged_range: u32,
+    ranged_power: i32,
+    speed: i32,
+    #[serde(deserialize_with = "de_intrinsics")]
     intrinsics: Vec<Intrinsic>,
+    /// Probability for the item to spawn cursed.
+    curse_chance: f32,
+    requirements: Requirements,
+    /// Effects applied to creatures hit with the item.
+    on_hit: Vec<OnHit>,
+    /// Passive effects when the item is equipped.
+    auras: Vec<Aura>,
 }
 
 impl Default for ItemSpec {
@@ -100,195 +165,313 @@ impl Default for ItemSpec {
             armor: 0,
             attack: 0,
             defense: 0,
+            ranged_range: 0,
+            ranged_power: 0,
+            speed: 0,
             intrinsics: Vec::new(),
+            curse_chance: 0.0,
+            requirements: Requirements::default(),
+            on_hit: Vec::new(),
+            auras: Vec::new(),
         }
     }
 }
 
 impl Distribution<Loadout> for ItemSpec {
-    fn sample(&self, _: &mut Rng) -> Loadout {
+    fn sample(&self, rng: &mut Rng) -> Loadout { self.sample_at(rng, self.depth) }
+}
+
+impl Spec for ItemSpec {
+    fn rarity(&self) -> f32 { self.rarity }
+    fn min_depth(&self) -> i32 { self.depth }
+    fn name(&self) -> &str { &self.name }
+    fn icon(&self) -> Icon { self.icon }
+    fn item_type(&self) -> Option<ItemType> { Some(self.item_type) }
+
+    fn sample_at(&self, rng: &mut Rng, depth: i32) -> Loadout {
+        let charges = match self.item_type {
+            // Amount of gold in the pile.
+            ItemType::Gold => rng.gen_range(1, self.power as u32 + 1),
+            // Number of zaps in a wand.
+            ItemType::TargetedUsable(_) => self.power.max(1) as u32,
+            _ => 1,
+        };
+        let cursed = self.curse_chance > 0.0 && rng.with_chance(self.curse_chance);
+
+        let mut desc = Desc::new(&self.name, self.icon);
+        let mut stats = Stats::new(self.power, &self.intrinsics)
+            .armor(self.armor)
+            .attack(self.attack)
+            .defense(self.defense)
+            .ranged_range(self.ranged_range)
+            .ranged_power(self.ranged_power)
+            .speed(self.speed);
+        let mut enchantment = 0;
+        let mut affix = None;
+
+        // Spells don't come in better or worse versions.
+        if let Some(equip_type) = self.item_type.equip_type().filter(|&t| t != EquipType::Spell) {
+            enchantment = roll_enchantment(rng, depth);
+            if cursed {
+                // Cursed items are always bad.
+                enchantment = -enchantment.max(1);
+            }
+            stats = stats + enchantment_stats(equip_type, enchantment);
+            if enchantment != 0 {
+                desc.prefix = format!("{:+}", enchantment);
+            }
+
+            affix = roll_affix(rng, equip_type);
+            if let Some(affix) = affix {
+                stats = stats + affix.stats();
+                desc.suffix = affix.suffix().to_string();
+            }
+        }
+
         Loadout::new()
-            .c(Desc::new(&self.name, self.icon))
-            .c(StatsComponent::new(
-                Stats::new(self.power, &self.intrinsics)
-                    .armor(self.armor)
-                    .attack(self.attack)
-                    .defense(self.defense),
-            ))
+            .c(desc)
+            .c(StatsComponent::new(stats))
             .c(Item {
                 item_type: self.item_type,
-                charges: 1,
+                charges,
+                quantity: 1,
+                cursed,
+                requirements: self.requirements,
+                enchantment,
+                affix,
+                on_hit: self.on_hit.clone(),
+                auras: self.auras.clone(),
             })
     }
 }
 
-impl Spec for ItemSpec {
-    fn rarity(&self) -> f32 { self.rarity }
-    fn min_depth(&self) -> i32 { self.depth }
-    fn name(&self) -> &str { &self.name }
+/// Roll a random enchantment bonus for equipment found at depth.
+///
+/// Enchantments get more common and stronger deeper in the dungeon.
+fn roll_enchantment(rng: &mut Rng, depth: i32) -> i32 {
+    if rng.gen_range(0, 10) >= depth.clamp(1, 6) {
+        return 0;
+    }
+    let max_bonus = (1 + depth / 3).min(3);
+    rng.gen_range(1, max_bonus + 1)
 }
 
-macro_rules! specs {
-    {$($item:expr,)+}
-    =>
-    {
-        lazy_static! {
-            pub static ref SPECS: HashMap<EntitySpawn, Arc<dyn Spec>> = {
-                let mut ret: HashMap<EntitySpawn, Arc<dyn Spec>> = HashMap::new();
-                $(ret.insert(EntitySpawn($item.name().to_string()), Arc::new($item));)+
-                ret
-            };
-        }
+/// Return the stats an enchantment adds to the type of equipment.
+fn enchantment_stats(equip_type: EquipType, enchantment: i32) -> Stats {
+    match equip_type {
+        EquipType::Melee => Stats::default().attack(enchantment),
+        EquipType::Ranged => Stats::default().ranged_power(enchantment),
+        EquipType::Head | EquipType::Body | EquipType::Feet => Stats::default().armor(enchantment),
+        EquipType::Spell | EquipType::Trinket => Stats::default(),
+    }
+}
+
+/// Occasionally roll a named affix for the type of equipment.
+fn roll_affix(rng: &mut Rng, equip_type: EquipType) -> Option<Affix> {
+    const AFFIX_CHANCE: u32 = 8;
+
+    if !rng.one_chance_in(AFFIX_CHANCE) {
+        return None;
     }
+    let choices: Vec<Affix> = Affix::iter().cloned().filter(|a| a.fits(equip_type)).collect();
+    choices.choose(rng).cloned()
+}
+
+/// Spec data files compiled into the game.
+const SPEC_FILES: &[(&str, &str)] = &[
+    ("biomes.ron", include_str!("../assets/specs/biomes.ron")),
+    ("items.ron", include_str!("../assets/specs/items.ron")),
+    ("mobs.ron", include_str!("../assets/specs/mobs.ron")),
+];
+
+/// Directory for data files that replace the compiled-in ones with the same file name.
+///
+/// Looked up next to the executable and in the working directory, so spec balance can be tweaked
+/// without rebuilding the game.
+pub const SPEC_OVERRIDE_DIR: &str = "assets/specs";
+
+lazy_static! {
+    static ref SPEC_DB: SpecDb = load_specs(override_dir().as_deref()).unwrap_or_else(|e| {
+        error!("Failed to load spec overrides, using builtin specs: {}", e);
+        builtin_specs()
+    });
+    pub static ref SPECS: HashMap<EntitySpawn, Arc<dyn Spec>> = SPEC_DB
+        .specs
+        .iter()
+        .map(|s| (EntitySpawn(s.name().to_string()), s.clone()))
+        .collect();
 }
 
 pub fn iter_specs() -> impl Iterator<Item = Arc<dyn Spec + 'static>> { SPECS.values().cloned() }
 
-use self::Intrinsic::*;
-use self::ShoutType::*;
-use crate::item::MagicEffect::*;
-use crate::Icon as I;
-use crate::ItemType::*;
-
-specs! {
-    // Mobs
-    MobSpec {
-        name: "player".into(),
-        icon: I::Player,
-        rarity: 0.0,
-        power: 10,
-        intrinsics: vec![Hands],
-        shout: Shout,
-        ..d()
-    },
-    MobSpec {
-        name: "dreg".into(),
-        icon: I::Dreg,
-        power: 2,
-        intrinsics: vec![Hands],
-        shout: Shout,
-        ..d()
-    },
-    MobSpec {
-        name: "snake".into(),
-        icon: I::Snake,
-        power: 1,
-        shout: Hiss,
-        ..d()
-    },
-    MobSpec {
-        name: "ooze".into(),
-        icon: I::Ooze,
-        depth: 1,
-        power: 3,
-        shout: Gurgle,
-        ..d()
-    },
-    MobSpec {
-        name: "bug".into(),
-        icon: I::Bug,
-        depth: 2,
-        rarity: 10.0,
-        power: 2,
-        ..d()
-    },
-    MobSpec {
-        name: "octopus".into(),
-        icon: I::Octopus,
-        depth: 2,
-        power: 5,
-        intrinsics: vec![Hands],
-        ..d()
-    },
-    MobSpec {
-        name: "ogre".into(),
-        icon: I::Ogre,
-        depth: 4,
-        rarity: 4.0,
-        power: 7,
-        intrinsics: vec![Hands],
-        shout: Shout,
-        ..d()
-    },
-    MobSpec {
-        name: "wraith".into(),
-        icon: I::Wraith,
-        depth: 5,
-        rarity: 6.0,
-        power: 10,
-        intrinsics: vec![Hands],
-        ..d()
-    },
-    MobSpec {
-        name: "efreet".into(),
-        icon: I::Efreet,
-        depth: 7,
-        rarity: 8.0,
-        power: 14,
-        intrinsics: vec![Hands],
-        ..d()
-    },
-    MobSpec {
-        name: "serpent".into(),
-        icon: I::Serpent,
-        depth: 9,
-        rarity: 10.0,
-        power: 20,
-        shout: Hiss,
-        ..d()
-    },
-
-
-    // Items
-    ItemSpec {
-        name: "sword".into(),
-        icon: I::Sword,
-        item_type: MeleeWeapon,
-        rarity: 10.0,
-        attack: 6,
-        ..d()
-    },
-    ItemSpec {
-        name: "helmet".into(),
-        icon: I::Helmet,
-        item_type: Helmet,
-        rarity: 10.0,
-        armor: 2,
-        ..d()
-    },
-    ItemSpec {
-        name: "armor".into(),
-        icon: I::Armor,
-        item_type: Armor,
-        rarity: 10.0,
-        armor: 5,
-        ..d()
-    },
-    ItemSpec {
-        name: "wand of fireball".into(),
-        icon: I::Wand1,
-        power: 5,
-        item_type: TargetedUsable(Fireball),
-        rarity: 10.0,
-        depth: 3,
-        ..d()
-    },
-    ItemSpec {
-        name: "wand of confusion".into(),
-        icon: I::Wand2,
-        power: 5,
-        item_type: TargetedUsable(Confuse),
-        rarity: 10.0,
-        armor: 5,
-        ..d()
-    },
-    ItemSpec {
-        name: "scroll of lightning".into(),
-        icon: I::Scroll1,
-        power: 1,
-        item_type: UntargetedUsable(Lightning),
-        ..d()
-    },
+pub fn iter_biomes() -> impl Iterator<Item = &'static BiomeSpec> { SPEC_DB.biomes.iter() }
+
+/// Everything loaded from the spec data files.
+pub struct SpecDb {
+    pub specs: Vec<Arc<dyn Spec>>,
+    pub biomes: Vec<BiomeSpec>,
+}
+
+/// Contents of a single spec data file.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SpecFile {
+    mobs: Vec<MobSpec>,
+    items: Vec<ItemSpec>,
+    biomes: Vec<BiomeSpec>,
+}
+
+/// Error from loading the spec database from data files.
+#[derive(Debug)]
+pub enum SpecError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ron::de::Error),
+    DuplicateName(PathBuf, String),
+    MissingSpec(EntitySpawn),
+    UnknownSpec(PathBuf, String),
+    MissingBiome(String),
+    TooManyKinds(ItemClass),
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpecError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            SpecError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            SpecError::DuplicateName(path, name) => {
+                write!(f, "{}: duplicate spec name \"{}\"", path.display(), name)
+            }
+            SpecError::MissingSpec(name) => write!(f, "required spec \"{}\" not defined", name),
+            SpecError::UnknownSpec(path, name) => {
+                write!(f, "{}: spawn table refers to unknown spec \"{}\"", path.display(), name)
+            }
+            SpecError::MissingBiome(name) => write!(f, "required biome \"{}\" not defined", name),
+            SpecError::TooManyKinds(class) => {
+                write!(f, "more {:?} kinds than appearances for them", class)
+            }
+        }
+    }
+}
+
+impl Error for SpecError {}
+
+/// Spec database from the data files compiled into the game.
+pub fn builtin_specs() -> SpecDb {
+    let sources = SPEC_FILES.iter().map(|&(name, text)| (Path::new(name), text));
+    parse_specs(sources).expect("Builtin spec data is broken")
+}
+
+/// Find the spec override directory, if there is one.
+fn override_dir() -> Option<PathBuf> {
+    let exe_dir = env::current_exe().ok().and_then(|p| p.parent().map(Path::to_path_buf));
+    exe_dir
+        .into_iter()
+        .chain(env::current_dir().ok())
+        .map(|d| d.join(SPEC_OVERRIDE_DIR))
+        .find(|d| d.is_dir())
+}
+
+/// Load the spec database, letting `.ron` files in the override directory replace the builtin
+/// data files with the same name.
+pub fn load_specs(override_dir: Option<&Path>) -> Result<SpecDb, SpecError> {
+    let mut sources: BTreeMap<PathBuf, Cow<'_, str>> = SPEC_FILES
+        .iter()
+        .map(|&(name, text)| (PathBuf::from(name), Cow::Borrowed(text)))
+        .collect();
+
+    if let Some(dir) = override_dir {
+        for entry in fs::read_dir(dir).map_err(|e| SpecError::Io(dir.to_path_buf(), e))? {
+            let path = entry.map_err(|e| SpecError::Io(dir.to_path_buf(), e))?.path();
+            if !path.extension().map_or(false, |x| x == "ron") {
+                continue;
+            }
+            let text = fs::read_to_string(&path).map_err(|e| SpecError::Io(path.clone(), e))?;
+            if let Some(name) = path.file_name() {
+                sources.remove(Path::new(name));
+            }
+            sources.insert(path, Cow::Owned(text));
+        }
+    }
+
+    // The map keeps the files in a fixed order so errors come out the same every time.
+    parse_specs(sources.iter().map(|(path, text)| (path.as_path(), text.as_ref())))
+}
+
+/// Parse spec data file contents into a spec database.
+fn parse_specs<'a>(
+    sources: impl IntoIterator<Item = (&'a Path, &'a str)>,
+) -> Result<SpecDb, SpecError> {
+    let mut names = HashSet::new();
+    let mut ret: Vec<Arc<dyn Spec>> = Vec::new();
+    let mut biomes: Vec<(&Path, BiomeSpec)> = Vec::new();
+    for (path, text) in sources {
+        let file: SpecFile =
+            ron::de::from_str(text).map_err(|e| SpecError::Parse(path.to_path_buf(), e))?;
+        let specs = file
+            .mobs
+            .into_iter()
+            .map(|s| Arc::new(s) as Arc<dyn Spec>)
+            .chain(file.items.into_iter().map(|s| Arc::new(s) as Arc<dyn Spec>));
+        for spec in specs {
+            if !names.insert(spec.name().to_string()) {
+                return Err(SpecError::DuplicateName(path.to_path_buf(), spec.name().to_string()));
+            }
+            ret.push(spec);
+        }
+        for biome in file.biomes {
+            if biomes.iter().any(|(_, b)| b.name() == biome.name()) {
+                return Err(SpecError::DuplicateName(path.to_path_buf(), biome.name().to_string()));
+            }
+            biomes.push((path, biome));
+        }
+    }
+
+    // The game can't start without a player.
+    if !names.contains(&PLAYER_SPAWN.0) {
+        return Err(SpecError::MissingSpec(PLAYER_SPAWN.clone()));
+    }
+    // Every unidentified magic item kind needs an appearance of its own.
+    for &class in &[ItemClass::Potion, ItemClass::Scroll, ItemClass::Wand] {
+        let kinds = ret.iter().filter(|s| ItemClass::from_icon(s.icon()) == Some(class)).count();
+        if kinds > class.appearances().len() {
+            return Err(SpecError::TooManyKinds(class));
+        }
+    }
+    if !biomes.iter().any(|(_, b)| b.name() == DUNGEON_BIOME) {
+        return Err(SpecError::MissingBiome(DUNGEON_BIOME.to_string()));
+    }
+    // Spawn tables can refer to specs in any file, so check them once everything is loaded.
+    for (path, biome) in &biomes {
+        if let Some(s) = biome.spawn_names().find(|s| !names.contains(&s.0)) {
+            return Err(SpecError::UnknownSpec(path.to_path_buf(), s.0.clone()));
+        }
+    }
+
+    Ok(SpecDb {
+        specs: ret,
+        biomes: biomes.into_iter().map(|(_, b)| b).collect(),
+    })
+}
+
+fn de_icon<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Icon, D::Error> {
+    serde::Deserialize::deserialize(d)
+        .map_err(|e| serde::de::Error::custom(format_args!("invalid icon: {}", e)))
+}
+
+fn de_intrinsics<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<Intrinsic>, D::Error> {
+    serde::Deserialize::deserialize(d)
+        .map_err(|e| serde::de::Error::custom(format_args!("invalid intrinsic: {}", e)))
+}
+
+/// Deserialize spawn names in data that's loaded along with the spec database.
+///
+/// The names can't be looked up before the database is complete, so they must be checked
+/// separately after loading.
+pub(crate) fn de_spawn_names<'de, D: serde::Deserializer<'de>>(
+    d: D,
+) -> Result<Vec<EntitySpawn>, D::Error> {
+    let names: Vec<String> = serde::Deserialize::deserialize(d)?;
+    Ok(names.into_iter().map(EntitySpawn).collect())
 }
 
 /// String that's guaranteed to describe an entity spawn.
@@ -335,25 +518,26 @@ impl serde::Serialize for EntitySpawn {
 impl<'a> serde::Deserialize<'a> for EntitySpawn {
     fn deserialize<D: serde::Deserializer<'a>>(d: D) -> Result<Self, D::Error> {
         let s: String = serde::Deserialize::deserialize(d)?;
-        Ok(EntitySpawn::from_str(&s).map_err(serde::de::Error::custom)?)
+        EntitySpawn::from_str(&s).map_err(serde::de::Error::custom)
     }
 }
 
-impl Distribution<Loadout> for EntitySpawn {
-    fn sample(&self, rng: &mut Rng) -> Loadout {
+impl EntitySpawn {
+    pub(crate) fn spec(&self) -> &Arc<dyn Spec> {
         SPECS
             .get(self)
-            .expect(&format!(
-                "EntitySpawn {:?} not found in spec database",
-                self
-            ))
-            .sample(rng)
+            .unwrap_or_else(|| panic!("EntitySpawn {:?} not found in spec database", self))
     }
-}
 
-// Helpers for data conciseness.
+    /// Generate the entity for a specific dungeon depth.
+    pub fn sample_at(&self, rng: &mut Rng, depth: i32) -> Loadout {
+        self.spec().sample_at(rng, depth)
+    }
+}
 
-fn d<T: Default>() -> T { Default::default() }
+impl Distribution<Loadout> for EntitySpawn {
+    fn sample(&self, rng: &mut Rng) -> Loadout { self.spec().sample(rng) }
+}
 
 #[cfg(test)]
 mod test {
@@ -371,12 +555,101 @@ mod test {
         assert_eq!(example, ron::de::from_str::<EntitySpawn>(&ser).unwrap());
 
         // Names in spec database get deserialized.
-        assert_eq!(
-            ron::de::from_str::<EntitySpawn>(&"\"dreg\"".to_string()).unwrap(),
-            example
-        );
+        assert_eq!(ron::de::from_str::<EntitySpawn>("\"dreg\"").unwrap(), example);
 
         // Names not in database don't.
-        assert!(ron::de::from_str::<EntitySpawn>(&"\"tyop txet\"".to_string()).is_err());
+        assert!(ron::de::from_str::<EntitySpawn>("\"tyop txet\"").is_err());
+    }
+
+    #[test]
+    fn test_shipped_specs() {
+        use super::{builtin_specs, SPEC_FILES};
+        use std::fs;
+
+        let db = builtin_specs();
+        assert!(db.specs.len() > 1);
+        assert!(!db.biomes.is_empty());
+
+        // Every data file in the assets directory must be compiled into the game.
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/specs");
+        for entry in fs::read_dir(dir).expect("Spec asset directory not found") {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            if name.ends_with(".ron") {
+                assert!(SPEC_FILES.iter().any(|&(n, _)| n == name), "{} not embedded", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_spec_overrides() {
+        use super::{load_specs, SPEC_FILES};
+        use std::fs;
+
+        let mobs = SPEC_FILES.iter().find(|&&(n, _)| n == "mobs.ron").unwrap().1;
+        let dir = std::env::temp_dir().join(format!("magog-spec-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("mobs.ron"), mobs.replace("mobs: [", "mobs: [(name: \"gnome\"),"))
+            .unwrap();
+        fs::write(dir.join("notes.txt"), "not a spec file").unwrap();
+        let db = load_specs(Some(&dir));
+        // Removing a spec the builtin spawn tables use breaks the database.
+        fs::write(dir.join("mobs.ron"), "(mobs: [(name: \"player\")])").unwrap();
+        let err = load_specs(Some(&dir)).err().map(|e| e.to_string()).unwrap_or_default();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // The override file replaces the builtin file with the same name, other files stay.
+        let names: Vec<String> =
+            db.expect("Override failed").specs.iter().map(|s| s.name().to_string()).collect();
+        assert!(names.contains(&"gnome".to_string()));
+        assert!(names.contains(&"dreg".to_string()));
+        assert!(names.contains(&"sword".to_string()));
+
+        assert_eq!(err, "biomes.ron: spawn table refers to unknown spec \"dreg\"");
+    }
+
+    #[test]
+    fn test_spec_load_errors() {
+        use super::parse_specs;
+        use std::path::Path;
+
+        let load = |text: &str| {
+            parse_specs(vec![(Path::new("test.ron"), text)])
+                .err()
+                .map(|e| e.to_string())
+                .unwrap_or_default()
+        };
+
+        let err = load("(mobs: [(name: \"player\", icon: Dragon)])");
+        assert!(err.starts_with("test.ron: invalid icon"), "{}", err);
+
+        let err = load("(mobs: [(name: \"player\", icon: Player, intrinsics: [Flying])])");
+        assert!(err.starts_with("test.ron: invalid intrinsic"), "{}", err);
+
+        let err = load("(mobs: [(name: \"player\"), (name: \"player\")])");
+        assert_eq!(err, "test.ron: duplicate spec name \"player\"");
+
+        let err = load("(items: [(name: \"sword\")])");
+        assert_eq!(err, "required spec \"player\" not defined");
+
+        let potions: Vec<String> =
+            (0..20).map(|i| format!("(name: \"potion {}\", icon: Potion1)", i)).collect();
+        let err = load(&format!("(mobs: [(name: \"player\")], items: [{}])", potions.join(",")));
+        assert_eq!(err, "more Potion kinds than appearances for them");
+
+        let err = load("(mobs: [(name: \"player\")])");
+        assert_eq!(err, "required biome \"dungeon\" not defined");
+
+        let biome = |groups: &str| {
+            format!(
+                "(mobs: [(name: \"player\")],
+                  biomes: [(name: \"dungeon\", monsters: [], items: [{}], monster_chance: 0.5)])",
+                groups
+            )
+        };
+        let err = load(&biome("(weight: 1.0, specs: [\"sword\"])"));
+        assert_eq!(err, "test.ron: spawn table refers to unknown spec \"sword\"");
+
+        assert_eq!(load(&biome("(weight: 1.0, specs: [\"player\"])")), "");
     }
 }
@@ -22,6 +22,8 @@ pub struct Stats {
     pub ranged_range: u32,
     /// Ranged attack power
     pub ranged_power: i32,
+    /// Speed bonus as percentage of normal speed
+    pub speed: i32,
 
     /// Bit flags for intrinsics
     pub intrinsics: u32,
@@ -54,6 +56,7 @@ impl Stats {
             ..self
         }
     }
+    pub fn speed(self, speed: i32) -> Stats { Stats { speed, ..self } }
 
     pub fn add_intrinsic(&mut self, intrinsic: Intrinsic) {
         self.intrinsics |= 1 << intrinsic as u32;
@@ -76,6 +79,7 @@ impl Add<Stats> for Stats {
             // type dealie.
             ranged_range: self.ranged_range + other.ranged_range,
             ranged_power: self.ranged_power + other.ranged_power,
+            speed: self.speed + other.speed,
             intrinsics: self.intrinsics | other.intrinsics,
         }
     }
@@ -92,4 +96,22 @@ pub enum Intrinsic {
     Hands,
     /// Explodes on death
     Deathsplosion,
+    /// Leaves a corpse on death.
+    LeavesCorpse,
+    /// Melee hits poison the target.
+    Venomous,
+    /// Melee hits set the target on fire.
+    Fiery,
+    /// Takes half damage from fire.
+    ResistFire,
+    /// Takes half damage from electricity.
+    ResistElectricity,
+    /// Takes half damage from cold.
+    ResistCold,
+    /// Takes double damage from fire.
+    VulnerableFire,
+    /// Takes double damage from electricity.
+    VulnerableElectricity,
+    /// Takes double damage from cold.
+    VulnerableCold,
 }
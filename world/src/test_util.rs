@@ -0,0 +1,73 @@
+//! Fixtures shared by the test modules.
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::item::{ItemType, Slot};
+use crate::location::Location;
+use crate::mutate::Mutate;
+use crate::query::Query;
+use crate::spec::EntitySpawn;
+use crate::world::World;
+use crate::Distribution;
+use calx::{Dir6, Incremental};
+use calx_ecs::Entity;
+
+/// Create a world with everything except the player removed.
+pub fn peaceful_world(seed: u32) -> World {
+    let mut world = World::new(seed);
+    let player = world.player().unwrap();
+    for e in world.active_mobs() {
+        if e != player {
+            world.kill_entity(e);
+        }
+    }
+    world
+}
+
+/// Run world updates until it's the player's turn again.
+pub fn wait_for_player(world: &mut World) {
+    for _ in 0..1000 {
+        if world.player_can_act() {
+            return;
+        }
+        world.update(&Command::Wait);
+    }
+    panic!("Player never got to act");
+}
+
+/// Spawn a new entity from a spec.
+pub fn spawn(world: &mut World, name: &str, loc: Location) -> Entity {
+    let loadout = name.parse::<EntitySpawn>().unwrap().sample(world.rng());
+    world.spawn(&loadout, loc)
+}
+
+/// Put a new item in the player's bag and return its slot.
+pub fn give_item(world: &mut World, name: &str) -> Slot {
+    let player = world.player().unwrap();
+    let item = spawn(world, name, world.location(player).unwrap());
+    let slot = world.free_bag_slot(player).unwrap();
+    world.equip_item(item, player, slot);
+    slot
+}
+
+/// Spawn a mob next to the player and return the direction to it.
+pub fn spawn_next_to_player(world: &mut World, name: &str) -> (Entity, Dir6) {
+    let player = world.player().unwrap();
+    let dir = *Dir6::iter().find(|&&d| world.can_step(player, d)).unwrap();
+    let loc = world.location(player).unwrap().jump(world, dir);
+    (spawn(world, name, loc), dir)
+}
+
+/// Change the type of the item in the player's slot.
+pub fn set_item_type(world: &mut World, slot: Slot, item_type: ItemType) {
+    let item = world.entity_equipped(world.player().unwrap(), slot).unwrap();
+    world.ecs_mut().item[item].item_type = item_type;
+}
+
+/// Return whether a message with the text was sent during the last update.
+pub fn saw_message(world: &World, text: &str) -> bool {
+    world.events().iter().any(|e| match e {
+        Event::Msg(msg) => msg == text,
+        _ => false,
+    })
+}
@@ -1,7 +1,9 @@
 use crate::fov::SphereVolumeFov;
 use crate::location::Location;
+use crate::terraform::TerrainQuery;
 use crate::world::World;
-use calx::HexFov;
+use calx::{Dir6, HexFov};
+use std::collections::{BinaryHeap, HashSet};
 use std::iter::FromIterator;
 
 /// `Volume` is a specific area of the game world.
@@ -20,4 +22,41 @@ impl Volume {
             HexFov::new(SphereVolumeFov::new(w, radius, origin)).map(|(pos, a)| a.origin + pos),
         ))
     }
+
+    /// Construct the volume a noise of given loudness spreads through.
+    ///
+    /// Noise follows portals and loses one point of loudness for every step. Terrain that blocks
+    /// sight, like walls and closed doors, muffles the noise and costs `WALL_DAMPENING` points to
+    /// pass through.
+    pub fn noise<T: TerrainQuery>(ctx: &T, origin: Location, loudness: u32) -> Volume {
+        const WALL_DAMPENING: u32 = 4;
+
+        let mut ret = Vec::new();
+        let mut reached = HashSet::new();
+        // Max-heap, so the loudest remaining locations get expanded first.
+        let mut edge = BinaryHeap::new();
+        edge.push((loudness, origin));
+
+        while let Some((left, loc)) = edge.pop() {
+            if !reached.insert(loc) {
+                continue;
+            }
+            ret.push(loc);
+
+            for &dir in Dir6::iter() {
+                let next = loc.jump(ctx, dir);
+                let cost = if ctx.terrain(next).blocks_sight() {
+                    WALL_DAMPENING
+                } else {
+                    1
+                };
+
+                if left >= cost && !reached.contains(&next) {
+                    edge.push((left - cost, next));
+                }
+            }
+        }
+
+        Volume(ret)
+    }
 }
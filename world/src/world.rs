@@ -1,10 +1,11 @@
-use crate::components;
+use crate::components::{self, Icon};
 use crate::event::Event;
 use crate::flags::Flags;
 use crate::fov::SightFov;
-use crate::item::Slot;
+use crate::item::{self, Appearances, Slot};
 use crate::location::{Location, Portal, SECTOR_WIDTH};
 use crate::mutate::Mutate;
+use crate::overlay::Overlay;
 use crate::query::Query;
 use crate::spatial::{Place, Spatial};
 use crate::terraform::{Terraform, TerrainQuery};
@@ -33,6 +34,11 @@ calx_ecs::build_ecs! {
     item: components::Item,
     stats: components::StatsComponent,
     status: components::Statuses,
+    schedule: components::Schedule,
+    nutrition: components::Nutrition,
+    experience: components::Experience,
+    mana: components::Mana,
+    cooldown: components::Cooldown,
 }
 
 /// Toplevel game state object.
@@ -45,6 +51,8 @@ pub struct World {
     ecs: Ecs,
     /// Static startup game world
     worldgen: Worldgen,
+    /// Changes to the startup world made during play.
+    overlay: Overlay,
     /// Spatial index for game entities.
     spatial: Spatial,
     /// Global gamestate flags.
@@ -53,6 +61,9 @@ pub struct World {
     rng: Rng,
     /// Event queue
     pub(crate) events: Vec<Event>,
+    /// Unidentified item looks, derived from the seed so they aren't saved.
+    #[serde(skip)]
+    appearances: Appearances,
 }
 
 impl<'a> World {
@@ -61,20 +72,26 @@ impl<'a> World {
             version: GAME_VERSION.to_string(),
             ecs: Ecs::new(),
             worldgen: Worldgen::new(seed),
+            overlay: Overlay::new(),
             spatial: Spatial::new(),
             flags: Flags::new(),
             rng: seeded_rng(&seed),
             events: Vec::new(),
+            appearances: item::roll_appearances(seed),
         };
 
         // XXX: Clone to not run into borrow checker...
-        for (loc, spawn) in ret
+        for (loc, spawn, contents) in ret
             .worldgen
             .spawns()
             .cloned()
-            .collect::<Vec<(Location, Loadout)>>()
+            .collect::<Vec<(Location, Loadout, Vec<Loadout>)>>()
         {
-            ret.spawn(&spawn, loc);
+            let e = ret.spawn(&spawn, loc);
+            for item in &contents {
+                let item = ret.spawn(item, loc);
+                ret.place_in(item, e);
+            }
         }
 
         // TODO non-lexical borrow
@@ -85,16 +102,15 @@ impl<'a> World {
     }
 
     pub fn load<R: Read>(reader: &mut R) -> Result<World, Box<dyn Error>> {
-        let ret: ron::de::Result<World> = ron::de::from_reader(reader);
-        if let Ok(ref x) = ret {
-            if x.version != GAME_VERSION {
-                panic!(
-                    "Save game version {} does not match current version {}",
-                    x.version, GAME_VERSION
-                );
-            }
+        let mut ret: World = ron::de::from_reader(reader)?;
+        if ret.version != GAME_VERSION {
+            panic!(
+                "Save game version {} does not match current version {}",
+                ret.version, GAME_VERSION
+            );
         }
-        Ok(ret?)
+        ret.appearances = item::roll_appearances(ret.rng_seed());
+        Ok(ret)
     }
 
     pub fn save<W: Write>(&self, writer: &mut W) -> Result<(), Box<dyn Error>> {
@@ -116,7 +132,10 @@ impl TerrainQuery for World {
     }
 
     fn terrain(&self, loc: Location) -> Terrain {
-        let mut t = self.worldgen.get_terrain(loc);
+        let mut t = self
+            .overlay
+            .terrain(loc)
+            .unwrap_or_else(|| self.worldgen.get_terrain(loc));
 
         if t == Terrain::Door && self.has_mobs(loc) {
             // Standing in the doorway opens the door.
@@ -126,9 +145,16 @@ impl TerrainQuery for World {
         t
     }
 
-    fn portal(&self, loc: Location) -> Option<Location> { self.worldgen.get_portal(loc) }
+    fn portal(&self, loc: Location) -> Option<Location> {
+        match self.overlay.portal(loc) {
+            Some(portal) => portal.map(|p| loc + p),
+            None => self.worldgen.get_portal(loc),
+        }
+    }
 
-    fn is_untouched(&self, _loc: Location) -> bool { unimplemented!() }
+    fn is_untouched(&self, loc: Location) -> bool {
+        !self.overlay.contains(loc) && !self.worldgen.is_generated(loc)
+    }
 }
 
 impl Query for World {
@@ -152,8 +178,16 @@ impl Query for World {
 
     fn get_tick(&self) -> u64 { self.flags.tick }
 
+    fn gold(&self) -> u32 { self.flags.gold }
+
     fn rng_seed(&self) -> u32 { self.worldgen.seed() }
 
+    fn is_identified_kind(&self, name: &str) -> bool { self.flags.identified.contains(name) }
+
+    fn kind_appearance(&self, name: &str) -> Option<(String, Icon)> {
+        self.appearances.get(name).cloned()
+    }
+
     fn entities(&self) -> slice::Iter<'_, Entity> { self.ecs.iter() }
 
     fn entities_at(&self, loc: Location) -> Vec<Entity> { self.spatial.entities_at(loc) }
@@ -190,10 +224,20 @@ impl Mutate for World {
     fn equip_item(&mut self, e: Entity, parent: Entity, slot: Slot) {
         self.spatial.equip(e, parent, slot);
         self.rebuild_stats(parent);
+        // Light sources change how far the wearer sees.
+        self.do_fov(parent);
+    }
+
+    fn place_in(&mut self, e: Entity, container: Entity) {
+        self.spatial.insert(e, Place::In(container, None));
     }
 
     fn set_player(&mut self, player: Option<Entity>) { self.flags.player = player; }
 
+    fn add_gold(&mut self, amount: u32) { self.flags.gold += amount; }
+
+    fn identify_kind(&mut self, name: String) { self.flags.identified.insert(name); }
+
     fn spawn(&mut self, loadout: &Loadout, loc: Location) -> Entity {
         let e = loadout.make(&mut self.ecs);
         self.place_entity(e, loc);
@@ -217,7 +261,7 @@ impl Mutate for World {
             let range = if origin.z == 0 {
                 OVERLAND_FOV_RANGE
             } else {
-                DEFAULT_FOV_RANGE
+                DEFAULT_FOV_RANGE + self.light_radius(e)
             };
 
             let fov: HashSet<Location> = HashSet::from_iter(
@@ -250,15 +294,415 @@ impl Mutate for World {
 }
 
 impl Terraform for World {
-    fn set_terrain(&mut self, _loc: Location, _terrain: Terrain) {
-        unimplemented!();
+    fn set_terrain(&mut self, loc: Location, terrain: Terrain) {
+        // Only store actual differences from the generated world.
+        if terrain == self.worldgen.get_terrain(loc) {
+            self.overlay.clear_terrain(loc);
+        } else {
+            self.overlay.set_terrain(loc, terrain);
+        }
+    }
+
+    fn set_portal(&mut self, loc: Location, portal: Portal) {
+        let mut destination = loc + portal;
+        // Don't chain portals, point directly to the final destination instead.
+        if let Some(next) = self.portal(destination) {
+            destination = next;
+        }
+
+        if destination == loc {
+            return;
+        }
+
+        let portal = Portal::new(loc, destination);
+        if self.worldgen.get_portal(loc) == Some(destination) {
+            self.overlay.clear_portal(loc);
+        } else {
+            self.overlay.set_portal(loc, Some(portal));
+        }
+    }
+
+    fn remove_portal(&mut self, loc: Location) {
+        if self.worldgen.get_portal(loc).is_some() {
+            self.overlay.set_portal(loc, None);
+        } else {
+            self.overlay.clear_portal(loc);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::World;
+    use crate::components::{BrainState, Status};
+    use crate::effect::Damage;
+    use crate::item::{Affix, ItemType, Slot};
+    use crate::location::{Location, Portal};
+    use crate::mutate::Mutate;
+    use crate::query::Query;
+    use crate::spec::EntitySpawn;
+    use crate::terraform::{Terraform, TerrainQuery};
+    use crate::terrain::Terrain;
+    use crate::test_util::spawn;
+    use crate::volume::Volume;
+    use calx::Dir6;
+
+    /// Carve a straight open corridor into the solid rock of the surface level.
+    fn corridor(world: &mut World, len: i16) {
+        for x in 0..len {
+            world.set_terrain(Location::new(x, 0, 0), Terrain::Ground);
+        }
+    }
+
+    #[test]
+    fn test_terrain_overlay() {
+        let mut world = World::new(1);
+
+        // The surface level is untouched by worldgen.
+        let a = Location::new(10, 10, 0);
+        let b = Location::new(20, 20, 0);
+        assert!(world.is_untouched(a));
+        assert_eq!(world.terrain(a), Terrain::Rock);
+
+        world.set_terrain(a, Terrain::Ground);
+        world.set_portal(a, Portal::new(a, b));
+        assert!(!world.is_untouched(a));
+        assert_eq!(world.terrain(a), Terrain::Ground);
+        assert_eq!(world.portal(a), Some(b));
+
+        // Changes survive a save roundtrip even though worldgen is rebuilt from the seed.
+        let mut save = Vec::new();
+        world.save(&mut save).unwrap();
+        let mut world = World::load(&mut &save[..]).unwrap();
+        assert_eq!(world.terrain(a), Terrain::Ground);
+        assert_eq!(world.portal(a), Some(b));
+
+        // Resetting to generated values leaves the location untouched again.
+        world.set_terrain(a, Terrain::Rock);
+        world.remove_portal(a);
+        assert_eq!(world.portal(a), None);
+        assert!(world.is_untouched(a));
     }
 
-    fn set_portal(&mut self, _loc: Location, _portal: Portal) {
-        unimplemented!();
+    #[test]
+    fn test_noise_propagation() {
+        let mut world = World::new(1);
+        corridor(&mut world, 20);
+
+        let noise = Volume::noise(&world, Location::new(0, 0, 0), 6);
+        // Open corridor carries the noise.
+        assert!(noise.0.contains(&Location::new(6, 0, 0)));
+        assert!(!noise.0.contains(&Location::new(7, 0, 0)));
+        // Rock muffles it.
+        assert!(noise.0.contains(&Location::new(0, 1, 0)));
+        assert!(!noise.0.contains(&Location::new(0, 2, 0)));
     }
 
-    fn remove_portal(&mut self, _loc: Location) {
-        unimplemented!();
+    #[test]
+    fn test_noise_wakes_mobs() {
+        let mut world = World::new(1);
+        corridor(&mut world, 20);
+
+        let near = spawn(&mut world, "dreg", Location::new(5, 0, 0));
+        let far = spawn(&mut world, "dreg", Location::new(15, 0, 0));
+        assert_eq!(world.brain_state(near), Some(BrainState::Asleep));
+
+        let origin = Location::new(0, 0, 0);
+        world.make_noise(origin, 6);
+        assert_eq!(
+            world.brain_state(near),
+            Some(BrainState::Investigating(origin))
+        );
+        assert_eq!(world.brain_state(far), Some(BrainState::Asleep));
+    }
+
+    #[test]
+    fn test_flee() {
+        let mut world = World::new(1);
+        corridor(&mut world, 20);
+
+        let threat = spawn(&mut world, "ogre", Location::new(0, 0, 0));
+        let dreg = spawn(&mut world, "dreg", Location::new(3, 0, 0));
+        world.ecs_mut().health[dreg].wounds = world.max_hp(dreg);
+        world.set_brain_state(dreg, BrainState::Hunting(threat));
+
+        world.run_ai_for(dreg);
+        assert_eq!(world.brain_state(dreg), Some(BrainState::Fleeing(threat)));
+        assert_eq!(world.location(dreg), Some(Location::new(4, 0, 0)));
+
+        // Run to safety and recover.
+        for _ in 0..100 {
+            world.run_ai_for(dreg);
+            if world.brain_state(dreg) != Some(BrainState::Fleeing(threat)) {
+                break;
+            }
+        }
+        assert_eq!(world.brain_state(dreg), Some(BrainState::Hunting(threat)));
+        assert_eq!(world.hp(dreg), world.max_hp(dreg));
+    }
+
+    #[test]
+    fn test_flee_cornered() {
+        let mut world = World::new(1);
+        corridor(&mut world, 20);
+
+        let threat = spawn(&mut world, "ogre", Location::new(5, 0, 0));
+        let dreg = spawn(&mut world, "dreg", Location::new(0, 0, 0));
+        world.ecs_mut().health[dreg].wounds = world.max_hp(dreg);
+        world.set_brain_state(dreg, BrainState::Fleeing(threat));
+
+        // Nowhere to run at the end of the corridor.
+        world.run_ai_for(dreg);
+        assert_eq!(world.brain_state(dreg), Some(BrainState::Hunting(threat)));
+    }
+
+    #[test]
+    fn test_chaotic_brawl() {
+        let mut world = World::new(1);
+        corridor(&mut world, 20);
+
+        let ooze = spawn(&mut world, "ooze", Location::new(0, 0, 0));
+        let dreg = spawn(&mut world, "dreg", Location::new(3, 0, 0));
+
+        world.run_ai_for(ooze);
+        assert_eq!(world.brain_state(ooze), Some(BrainState::Hunting(dreg)));
+    }
+
+    #[test]
+    fn test_companion() {
+        let mut world = World::new(1);
+        corridor(&mut world, 20);
+
+        let player = world.player().unwrap();
+        world.place_entity(player, Location::new(0, 0, 0));
+        let hireling = spawn(&mut world, "hireling", Location::new(3, 0, 0));
+
+        // Companion joins the player when it sees them.
+        world.run_ai_for(hireling);
+        assert_eq!(
+            world.brain_state(hireling),
+            Some(BrainState::Following(player))
+        );
+
+        // And goes after the player's enemies.
+        let dreg = spawn(&mut world, "dreg", Location::new(6, 0, 0));
+        world.run_ai_for(hireling);
+        assert_eq!(world.brain_state(hireling), Some(BrainState::Hunting(dreg)));
+
+        // Player swaps places with companion instead of bumping into it.
+        world.kill_entity(dreg);
+        world.place_entity(hireling, Location::new(1, 0, 0));
+        // Direction towards increasing x.
+        let dir = Dir6::from_v2(euclid::vec2(1, 0));
+        assert!(world.can_step(player, dir));
+        world.entity_step(player, dir).unwrap();
+        assert_eq!(world.location(player), Some(Location::new(1, 0, 0)));
+        assert_eq!(world.location(hireling), Some(Location::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn test_damage_mitigation() {
+        let mut world = World::new(1);
+        corridor(&mut world, 20);
+
+        let efreet = spawn(&mut world, "efreet", Location::new(10, 0, 0));
+        world.damage(efreet, 6, Damage::Fire, None);
+        assert_eq!(world.ecs().health[efreet].wounds, 3);
+        world.damage(efreet, 4, Damage::Cold, None);
+        assert_eq!(world.ecs().health[efreet].wounds, 11);
+
+        // Armor soaks up physical damage but not elemental damage.
+        let ogre = spawn(&mut world, "ogre", Location::new(12, 0, 0));
+        assert_eq!(world.ecs().health[ogre].armor, 3);
+        world.damage(ogre, 2, Damage::Physical, None);
+        assert_eq!(world.ecs().health[ogre].wounds, 0);
+        world.damage(ogre, 2, Damage::Physical, None);
+        assert_eq!(world.ecs().health[ogre].wounds, 1);
+        assert_eq!(world.ecs().health[ogre].armor, 0);
+        world.damage(ogre, 2, Damage::Fire, None);
+        assert_eq!(world.ecs().health[ogre].wounds, 3);
+
+        // Worn armor gets repaired.
+        world.tick_regeneration(ogre);
+        assert!(world.ecs().health[ogre].armor > 0);
+    }
+
+    #[test]
+    fn test_death_effects() {
+        let mut world = World::new(1);
+        corridor(&mut world, 20);
+
+        // Dead mobs drop their stuff and leave a corpse.
+        let loc = Location::new(5, 0, 0);
+        let ogre = spawn(&mut world, "ogre", loc);
+        let sword = spawn(&mut world, "sword", Location::new(0, 0, 0));
+        world.equip_item(sword, ogre, Slot::Melee);
+        world.damage(ogre, 100, Damage::Cold, None);
+        world.clean_dead();
+        assert!(!world.is_alive(ogre));
+        assert!(world.is_alive(sword));
+        let items: Vec<_> = (0..20)
+            .flat_map(|x| world.entities_at(Location::new(x, 0, 0)))
+            .filter(|&e| world.is_item(e))
+            .collect();
+        assert_eq!(items.len(), 2);
+        assert!(items
+            .iter()
+            .any(|&e| world.item_type(e) == Some(ItemType::Corpse)));
+
+        // Exploding mobs take their neighbors with them, and the killer gets the credit.
+        let player = world.player().unwrap();
+        let efreet = spawn(&mut world, "efreet", Location::new(10, 0, 0));
+        let dreg = spawn(&mut world, "dreg", Location::new(11, 0, 0));
+        let bystander = spawn(&mut world, "dreg", Location::new(13, 0, 0));
+        let xp = world.xp_value(efreet) + world.xp_value(dreg);
+        world.damage(efreet, 100, Damage::Physical, Some(player));
+        assert!(!world.is_alive(efreet));
+        assert!(!world.is_alive(dreg));
+        assert!(world.is_alive(bystander));
+        assert_eq!(world.experience(player).unwrap().xp, xp);
+    }
+
+    #[test]
+    fn test_instant_items() {
+        let mut world = World::new(1);
+        corridor(&mut world, 20);
+        // Direction towards increasing x.
+        let dir = Dir6::from_v2(euclid::vec2(1, 0));
+
+        let player = world.player().unwrap();
+        world.place_entity(player, Location::new(0, 0, 0));
+        world.ecs_mut().health[player].wounds = 5;
+        let orb = spawn(&mut world, "healing orb", Location::new(1, 0, 0));
+        let gold = spawn(&mut world, "gold", Location::new(2, 0, 0));
+        let amount = world.uses_left(gold);
+
+        world.entity_step(player, dir).unwrap();
+        assert!(!world.is_alive(orb));
+        assert_eq!(world.ecs().health[player].wounds, 0);
+
+        world.entity_step(player, dir).unwrap();
+        assert!(!world.is_alive(gold));
+        assert_eq!(world.gold(), amount);
+
+        // Monsters use up instants too.
+        let dreg = spawn(&mut world, "dreg", Location::new(10, 0, 0));
+        world.ecs_mut().health[dreg].wounds = 2;
+        let orb = spawn(&mut world, "healing orb", Location::new(11, 0, 0));
+        world.entity_step(dreg, dir).unwrap();
+        assert!(!world.is_alive(orb));
+        assert_eq!(world.ecs().health[dreg].wounds, 0);
+    }
+
+    #[test]
+    fn test_statuses() {
+        let mut world = World::new(1);
+        corridor(&mut world, 20);
+        let player = world.player().unwrap();
+        world.place_entity(player, Location::new(0, 0, 0));
+
+        // Poison hurts over time and then wears off.
+        let ogre = spawn(&mut world, "ogre", Location::new(4, 0, 0));
+        world.gain_status(ogre, Status::Poisoned, 60);
+        for _ in 0..60 {
+            world.tick_statuses(ogre);
+        }
+        assert!(world.ecs().health[ogre].wounds > 0);
+        assert!(!world.has_status(ogre, Status::Poisoned));
+
+        // Sleepers don't act until they get hit.
+        world.gain_status(ogre, Status::Asleep, 100);
+        assert!(world.is_incapacitated(ogre));
+        world.damage(ogre, 1, Damage::Physical, None);
+        assert!(!world.has_status(ogre, Status::Asleep));
+
+        // Invisible creatures don't get noticed.
+        world.gain_status(player, Status::Invisible, 100);
+        world.run_ai_for(ogre);
+        assert_eq!(world.brain_state(ogre), Some(BrainState::Asleep));
+        world.lose_status(player, Status::Invisible);
+        world.run_ai_for(ogre);
+        assert_eq!(world.brain_state(ogre), Some(BrainState::Hunting(player)));
+    }
+
+    #[test]
+    fn test_scheduler() {
+        let mut world = World::new(1);
+        corridor(&mut world, 20);
+        let player = world.player().unwrap();
+        world.place_entity(player, Location::new(0, 0, 0));
+
+        assert_eq!(world.action_delay(player, 100), 12);
+        // Heavier actions take longer.
+        assert!(world.action_delay(player, 120) > world.action_delay(player, 100));
+
+        world.gain_status(player, Status::Hasted, 1000);
+        assert_eq!(world.action_delay(player, 100), 9);
+
+        // Speed modifiers from all sources stack without running out of range.
+        world.ecs_mut().stats[player].base.speed = 500;
+        world.rebuild_stats(player);
+        assert!(world.action_delay(player, 100) < 9);
+        world.ecs_mut().stats[player].base.speed = -500;
+        world.rebuild_stats(player);
+        world.gain_status(player, Status::Slowed, 1000);
+        assert!(world.action_delay(player, 100) > 36);
+
+        // The frontend can see how long it has to wait for the player's turn.
+        let delay = u64::from(world.action_delay(player, 100));
+        world.end_turn(player, 100);
+        assert!(!world.player_can_act());
+        assert_eq!(world.ticks_until_player_acts(), Some(delay));
+        for _ in 0..delay {
+            world.next_tick();
+        }
+        assert!(world.player_can_act());
+    }
+
+    #[test]
+    fn test_item_generation() {
+        let sword: EntitySpawn = "sword".parse().unwrap();
+        let mut rng = calx::seeded_rng(&1);
+
+        let mut count_enchanted = |depth| {
+            let mut ret = 0;
+            for _ in 0..200 {
+                let loadout = sword.sample_at(&mut rng, depth);
+                let item = loadout.item.unwrap();
+                let might = if item.affix == Some(Affix::Might) { 3 } else { 0 };
+                assert_eq!(loadout.stats.unwrap().base.attack, 6 + item.enchantment + might);
+                assert!(item.enchantment.abs() <= 3);
+                if item.enchantment > 0 {
+                    let prefix = format!("+{} sword", item.enchantment);
+                    assert!(loadout.desc.unwrap().full_name().starts_with(&prefix));
+                    ret += 1;
+                }
+            }
+            ret
+        };
+        // Enchantments are rare near the surface and common deep down.
+        assert!(count_enchanted(1) < 40);
+        assert!(count_enchanted(10) > 80);
+
+        // Affixes show up in the name and the rolled item survives saving.
+        let loadout = (0..1000)
+            .map(|_| sword.sample_at(&mut rng, 5))
+            .find(|l| l.item.as_ref().unwrap().affix.is_some())
+            .expect("No affixes rolled");
+        let mut world = World::new(1);
+        let e = world.spawn(&loadout, Location::new(0, 0, 0));
+        let name = world.entity_name(e);
+        let affix = loadout.item.unwrap().affix.unwrap();
+        assert!(name.ends_with(affix.suffix()));
+
+        let mut save = Vec::new();
+        world.save(&mut save).unwrap();
+        let world = World::load(&mut &save[..]).unwrap();
+        assert_eq!(world.entity_name(e), name);
+        assert_eq!(world.ecs().item[e].affix, Some(affix));
+        assert_eq!(
+            world.ecs().stats[e].base.attack,
+            loadout.stats.unwrap().base.attack
+        );
     }
 }
@@ -1,6 +1,7 @@
 //! Game world generation
 
 use crate::biome::{Biome, Dungeon};
+use crate::item::ItemType;
 use crate::location::{Location, Portal, Sector};
 use crate::map::{Map, MapCell};
 use crate::terrain::Terrain;
@@ -18,7 +19,8 @@ pub struct Worldgen {
     seed: u32,
     terrain: HashMap<Location, Terrain>,
     portals: HashMap<Location, Portal>,
-    spawns: Vec<(Location, Loadout)>,
+    /// Spawned entities along with the contents of containers.
+    spawns: Vec<(Location, Loadout, Vec<Loadout>)>,
     player_entry: Location,
 }
 
@@ -36,17 +38,18 @@ impl Worldgen {
 
         const NUM_FLOORS: i32 = 10;
 
-        let floors: Vec<Map> = (0..NUM_FLOORS)
+        let floors: Vec<(Biome, Map)> = (0..NUM_FLOORS)
             .map(|i| {
-                let map: Dungeon = Biome::new(i + 1).sample(&mut rng);
-                map.0
+                let biome = Biome::new(i + 1);
+                let map: Dungeon = biome.sample(&mut rng);
+                (biome, map.0)
             })
             .collect();
 
         for i in 0..floors.len() {
             let depth = (i + 1) as i16;
             let origin = Sector::new(0, 0, depth as i16).origin();
-            let map = &floors[i];
+            let (biome, map) = &floors[i];
 
             if depth == 1 {
                 ret.player_entry = origin + map.entrances()[0];
@@ -65,7 +68,16 @@ impl Worldgen {
                 }
 
                 for s in spawns {
-                    ret.spawns.push((loc, s.sample(&mut rng)))
+                    let loadout = s.sample_at(&mut rng, depth as i32);
+                    let contents = match loadout.item.as_ref().map(|i| i.item_type) {
+                        Some(ItemType::Container(capacity)) => biome
+                            .sample_loot(&mut rng, capacity)
+                            .iter()
+                            .map(|s| s.sample_at(&mut rng, depth as i32))
+                            .collect(),
+                        _ => Vec::new(),
+                    };
+                    ret.spawns.push((loc, loadout, contents))
                 }
             }
 
@@ -73,7 +85,7 @@ impl Worldgen {
             if i < floors.len() - 1 {
                 let mut other_origin = origin;
                 other_origin.z += 1;
-                let other_stairs = floors[i + 1].entrances();
+                let other_stairs = floors[i + 1].1.entrances();
 
                 for &stair in &map.exits() {
                     let other = *other_stairs.choose(&mut rng).unwrap();
@@ -97,11 +109,18 @@ impl Worldgen {
 
     fn default_terrain(&self, _loc: Location) -> Terrain { Terrain::Rock }
 
+    /// Return whether map generation has placed terrain or portals at location.
+    pub fn is_generated(&self, loc: Location) -> bool {
+        self.terrain.contains_key(&loc) || self.portals.contains_key(&loc)
+    }
+
     pub fn get_portal(&self, loc: Location) -> Option<Location> {
         self.portals.get(&loc).map(|&p| loc + p)
     }
 
-    pub fn spawns(&self) -> slice::Iter<'_, (Location, Loadout)> { self.spawns.iter() }
+    pub fn spawns(&self) -> slice::Iter<'_, (Location, Loadout, Vec<Loadout>)> {
+        self.spawns.iter()
+    }
 
     pub fn player_entry(&self) -> Location { self.player_entry }
 